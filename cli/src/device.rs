@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use crate::{
+    params,
+    port::Port,
+    protocol::{self, Command, DeviceError, Identity, BAD_SETTINGS, ERROR, PRESETS, REPLY},
+    setup::Setup,
+    Error, Result,
+};
+
+/// how long to wait for a reply. saving writes flash, which takes a while
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A matriu on the other end of a port
+pub struct Device<P: Port> {
+    pub port: P,
+}
+
+impl<P: Port> Device<P> {
+    pub fn new(port: P) -> Self {
+        Self { port }
+    }
+
+    /// Sends a request and waits for its reply, skipping any other messages
+    fn request(&mut self, command: Command, data: &[u8]) -> Result<Vec<u8>> {
+        let command = command as u8;
+        self.port.send(&protocol::message(command, data))?;
+        loop {
+            let message = self.port.receive(TIMEOUT)?.ok_or(Error::Timeout)?;
+            match protocol::parse(&message) {
+                Some((c, data)) if c == command | REPLY => return Ok(data),
+                Some((ERROR, data)) if data.first() == Some(&command) => {
+                    return Err(Error::Device(DeviceError {
+                        command,
+                        code: data.get(1).copied().unwrap_or(0),
+                    }))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Asks the device what it is, with the universal identity request
+    pub fn identity(&mut self) -> Result<Identity> {
+        self.port.send(&protocol::IDENTITY_REQUEST)?;
+        loop {
+            let message = self.port.receive(TIMEOUT)?.ok_or(Error::Timeout)?;
+            if let Some(identity) = protocol::parse_identity(&message) {
+                return Ok(identity);
+            }
+        }
+    }
+
+    /// Returns the firmware version as major, minor and patch, then the protocol version
+    pub fn version(&mut self) -> Result<[u8; 4]> {
+        let data = self.request(Command::Version, &[])?;
+        data.try_into().map_err(|_| Error::BadReply)
+    }
+
+    pub fn get(&mut self, id: u16) -> Result<u8> {
+        let [hi, lo] = id.to_be_bytes();
+        match self.request(Command::Get, &[hi, lo])?[..] {
+            [h, l, value] if [h, l] == [hi, lo] => Ok(value),
+            _ => Err(Error::BadReply),
+        }
+    }
+
+    /// Sets a param until the device is turned off, returning the value it took after clamping.
+    /// Call `save` to keep it
+    pub fn set(&mut self, id: u16, value: u8) -> Result<u8> {
+        let [hi, lo] = id.to_be_bytes();
+        match self.request(Command::Set, &[hi, lo, value])?[..] {
+            [h, l, value] if [h, l] == [hi, lo] => Ok(value),
+            _ => Err(Error::BadReply),
+        }
+    }
+
+    /// Returns every setting as `(id, value)` pairs
+    pub fn dump(&mut self) -> Result<Vec<(u16, u8)>> {
+        let data = self.request(Command::Dump, &[])?;
+        protocol::parse_dump(&data).ok_or(Error::BadReply)
+    }
+
+    /// Sets every setting in `pairs` and stores them in flash. Settings not in `pairs` are left alone
+    pub fn restore(&mut self, pairs: &[(u16, u8)]) -> Result<()> {
+        self.request(Command::Restore, &protocol::encode_dump(pairs))?;
+        Ok(())
+    }
+
+    /// Stores the current settings in flash
+    pub fn save(&mut self) -> Result<()> {
+        self.request(Command::Save, &[])?;
+        Ok(())
+    }
+
+    pub fn save_preset(&mut self, p: u8) -> Result<()> {
+        self.request(Command::SavePreset, &[p])?;
+        Ok(())
+    }
+
+    /// Loads preset `p`, returning false if nothing was saved there
+    pub fn load_preset(&mut self, p: u8) -> Result<bool> {
+        match self.request(Command::LoadPreset, &[p]) {
+            Ok(_) => Ok(true),
+            Err(Error::Device(DeviceError {
+                code: BAD_SETTINGS, ..
+            })) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the settings stored in preset `p` without loading it, or `None` if nothing was saved there
+    pub fn dump_preset(&mut self, p: u8) -> Result<Option<Vec<(u16, u8)>>> {
+        match self.request(Command::DumpPreset, &[p]) {
+            Ok(data) => protocol::parse_dump(&data).map(Some).ok_or(Error::BadReply),
+            Err(Error::Device(DeviceError {
+                code: BAD_SETTINGS, ..
+            })) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stores `pairs` as preset `p` without loading it
+    pub fn restore_preset(&mut self, p: u8, pairs: &[(u16, u8)]) -> Result<()> {
+        let mut data = vec![p];
+        data.extend(protocol::encode_dump(pairs));
+        self.request(Command::RestorePreset, &data)?;
+        Ok(())
+    }
+
+    /// Reads the current settings and every preset, without changing anything
+    pub fn backup(&mut self) -> Result<Setup> {
+        let mut setup = Setup {
+            current: self.dump()?,
+            presets: Vec::new(),
+        };
+        for p in 0..PRESETS {
+            if let Some(preset) = self.dump_preset(p)? {
+                setup.presets.push((p, preset));
+            }
+        }
+        Ok(setup)
+    }
+
+    /// Writes every preset in `setup`, then its current settings, storing each once
+    pub fn restore_setup(&mut self, setup: &Setup) -> Result<()> {
+        if !setup.presets.is_empty() {
+            // presets saved on the device hold every setting, so the ones a file leaves out
+            // come from the settings in use, like they would when saving the preset there
+            let current = self.dump()?;
+            for (p, preset) in &setup.presets {
+                let mut pairs = current.clone();
+                for &(id, value) in preset {
+                    match pairs.iter_mut().find(|pair| pair.0 == id) {
+                        Some(pair) => pair.1 = value,
+                        None => pairs.push((id, value)),
+                    }
+                }
+                self.restore_preset(*p, &pairs)?;
+            }
+        }
+        self.restore(&setup.current)
+    }
+
+    /// Describes the device and its main settings
+    pub fn status(&mut self) -> Result<String> {
+        let [major, minor, patch, protocol] = self.version()?;
+        let settings = self.dump()?;
+        let value = |name: &str| {
+            let id = params::id(name).unwrap();
+            let value = settings.iter().find(|p| p.0 == id).map_or(0, |p| p.1);
+            params::format_value(id, value)
+        };
+
+        let mut status = format!("matriu {major}.{minor}.{patch}, protocol {protocol}\n");
+        status += &format!("root {} {}\n", value("root"), value("scale"));
+        status += &format!(
+            "channel {}\n",
+            value("channel").parse::<u8>().unwrap_or(0) + 1
+        );
+        status += &format!("bpm {}\n", value("bpm"));
+        let zones: usize = value("zone_count").parse().unwrap_or(1);
+        for z in 0..zones {
+            let channel = match value(&format!("zone_channel.{z}")).parse::<u8>() {
+                Ok(c @ 0..=15) => (c + 1).to_string(),
+                _ => "global".to_string(),
+            };
+            status += &format!(
+                "zone {} from column {}: {}, octave {}, channel {channel}\n",
+                z + 1,
+                value(&format!("zone_start.{z}")),
+                value(&format!("zone_keyboard.{z}")),
+                value(&format!("zone_octave.{z}")),
+            );
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,54 @@
+//! Talks to the matriu over its sysex protocol, to back up, restore and set up devices from the computer.
+//! The protocol itself is implemented on the device in `src/sysex.rs`
+
+pub mod device;
+pub mod mock;
+pub mod params;
+pub mod port;
+pub mod protocol;
+pub mod setup;
+
+use std::{fmt, io};
+
+pub use device::Device;
+pub use mock::MockPort;
+pub use port::{Port, RawPort};
+pub use setup::Setup;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// the device didn't answer in time
+    Timeout,
+    /// the device answered with an error
+    Device(protocol::DeviceError),
+    /// the device answered something we can't make sense of
+    BadReply,
+    /// a setup file couldn't be read, with the line and what's wrong with it
+    BadFile(usize, String),
+    /// a param name or value that doesn't exist
+    Unknown(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Timeout => write!(f, "the device didn't answer"),
+            Error::Device(e) => write!(f, "{e}"),
+            Error::BadReply => write!(f, "the device sent a reply that doesn't make sense"),
+            Error::BadFile(line, e) => write!(f, "line {line}: {e}"),
+            Error::Unknown(s) => write!(f, "unknown {s}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
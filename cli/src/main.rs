@@ -0,0 +1,115 @@
+use std::{env, fs, process::ExitCode};
+
+use matriu_cli::{params, Device, Error, MockPort, Port, RawPort, Result, Setup};
+
+const USAGE: &str = "usage: matriu [--port PATH | --serial PATH | --mock] COMMAND
+
+the port is a raw midi device, like /dev/snd/midiC1D0, and can also be set with MATRIU_PORT.
+--serial talks over the matriu's serial port instead, like /dev/ttyACM0.
+--mock talks to a pretend device instead, to try things out
+
+commands:
+  status                 show the firmware version and the main settings
+  get NAME               show a setting, like root or zone_keyboard.1
+  set NAME VALUE         change a setting until the device is turned off
+  save                   store the current settings, so they're kept
+  backup FILE            write the settings and every preset to FILE
+  restore FILE           set the settings and presets in FILE
+  push FILE              set the settings in FILE, ignoring presets
+  save-preset N          store the current settings as preset N
+  load-preset N          load preset N";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut port = env::var("MATRIU_PORT").ok();
+    let mut mock = false;
+    let mut serial = false;
+    match args.first().map(String::as_str) {
+        Some("--port") if args.len() > 1 => {
+            port = Some(args.remove(1));
+            args.remove(0);
+        }
+        Some("--serial") if args.len() > 1 => {
+            port = Some(args.remove(1));
+            serial = true;
+            args.remove(0);
+        }
+        Some("--mock") => {
+            mock = true;
+            args.remove(0);
+        }
+        _ => {}
+    }
+
+    let result = if mock {
+        run(Device::new(MockPort::new()), &args)
+    } else if let Some(port) = port {
+        let port = if serial {
+            RawPort::open_serial(&port)
+        } else {
+            RawPort::open(&port)
+        };
+        port.map_err(Error::from)
+            .and_then(|port| run(Device::new(port), &args))
+    } else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs a command, returning false if the arguments don't make sense
+fn run<P: Port>(mut device: Device<P>, args: &[String]) -> Result<bool> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args[..] {
+        ["status"] => print!("{}", device.status()?),
+        ["get", name] => {
+            let id = param(name)?;
+            println!("{}", params::format_value(id, device.get(id)?));
+        }
+        ["set", name, value] => {
+            let id = param(name)?;
+            let value = params::parse_value(id, value)
+                .ok_or_else(|| Error::Unknown(format!("value {value} for {name}")))?;
+            println!("{}", params::format_value(id, device.set(id, value)?));
+        }
+        ["save"] => device.save()?,
+        ["backup", file] => fs::write(file, device.backup()?.to_string())?,
+        ["restore", file] => device.restore_setup(&read_setup(file)?)?,
+        ["push", file] => device.restore(&read_setup(file)?.current)?,
+        ["save-preset", p] => device.save_preset(preset(p)?)?,
+        ["load-preset", p] => {
+            if !device.load_preset(preset(p)?)? {
+                println!("nothing saved in preset {p}");
+            }
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn param(name: &str) -> Result<u16> {
+    params::id(name).ok_or_else(|| Error::Unknown(format!("param {name}")))
+}
+
+fn preset(p: &str) -> Result<u8> {
+    p.parse()
+        .ok()
+        .filter(|&p| p < matriu_cli::protocol::PRESETS)
+        .ok_or_else(|| Error::Unknown(format!("preset {p}")))
+}
+
+fn read_setup(file: &str) -> Result<Setup> {
+    Setup::parse(&fs::read_to_string(file)?)
+}
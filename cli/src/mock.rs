@@ -0,0 +1,187 @@
+use std::{collections::VecDeque, io, time::Duration};
+
+use crate::{
+    params,
+    port::Port,
+    protocol::{self, Command, ERROR, PRESETS, REPLY},
+};
+
+/// Answers requests the way the device does, keeping settings and presets in memory.
+/// Values aren't clamped like on the device, everything else should behave the same
+pub struct MockPort {
+    /// settings in use, as `(id, value)` pairs in id order
+    pub settings: Vec<(u16, u8)>,
+    /// settings stored in flash
+    pub stored: Vec<(u16, u8)>,
+    pub presets: [Option<Vec<(u16, u8)>>; PRESETS as usize],
+    pub version: [u8; 3],
+    /// every request received, to check what was sent
+    pub requests: Vec<Vec<u8>>,
+    replies: VecDeque<Vec<u8>>,
+}
+
+impl MockPort {
+    /// A device with every setting at 0 and no presets
+    pub fn new() -> Self {
+        let settings: Vec<_> = params::all().map(|id| (id, 0)).collect();
+        Self {
+            stored: settings.clone(),
+            settings,
+            presets: Default::default(),
+            version: [0, 1, 0],
+            requests: Vec::new(),
+            replies: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&self, id: u16) -> Option<u8> {
+        self.settings.iter().find(|p| p.0 == id).map(|p| p.1)
+    }
+
+    /// Sets every value in `pairs` that has a known id, like the device does with a dump
+    pub fn set(&mut self, pairs: &[(u16, u8)]) {
+        for &(id, value) in pairs {
+            if let Some(p) = self.settings.iter_mut().find(|p| p.0 == id) {
+                p.1 = value;
+            }
+        }
+    }
+
+    fn reply(&mut self, command: u8, data: &[u8]) {
+        self.replies
+            .push_back(protocol::message(command | REPLY, data));
+    }
+
+    fn error(&mut self, command: u8, code: u8) {
+        self.replies
+            .push_back(protocol::message(ERROR, &[command, code]));
+    }
+
+    fn handle(&mut self, command: u8, data: &[u8]) {
+        let Some(cmd) = Command::from_id(command) else {
+            return self.error(command, protocol::UNKNOWN_COMMAND);
+        };
+        let expected = match cmd {
+            Command::SavePreset | Command::LoadPreset | Command::DumpPreset => 1,
+            Command::Get => 2,
+            Command::Set => 3,
+            Command::Restore => data.len(),
+            Command::RestorePreset => data.len().max(1),
+            _ => 0,
+        };
+        if data.len() != expected {
+            return self.error(command, protocol::WRONG_LENGTH);
+        }
+
+        match cmd {
+            Command::Version => {
+                let [major, minor, patch] = self.version;
+                self.reply(command, &[major, minor, patch, protocol::PROTOCOL_VERSION]);
+            }
+            Command::Get | Command::Set => {
+                let id = u16::from_be_bytes([data[0], data[1]]);
+                if cmd == Command::Set {
+                    self.set(&[(id, data[2])]);
+                }
+                match self.get(id) {
+                    Some(value) => self.reply(command, &[data[0], data[1], value]),
+                    None => self.error(command, protocol::UNKNOWN_PARAM),
+                }
+            }
+            Command::Dump => {
+                let dump = protocol::encode_dump(&self.settings);
+                self.reply(command, &dump);
+            }
+            Command::Restore => match protocol::parse_dump(data) {
+                Some(pairs) => {
+                    self.set(&pairs);
+                    self.stored = self.settings.clone();
+                    self.reply(command, &[]);
+                }
+                None => self.error(command, protocol::BAD_SETTINGS),
+            },
+            Command::Save => {
+                self.stored = self.settings.clone();
+                self.reply(command, &[]);
+            }
+            Command::SavePreset | Command::LoadPreset => {
+                let p = data[0] as usize;
+                if p >= PRESETS as usize {
+                    return self.error(command, protocol::UNKNOWN_PRESET);
+                }
+                if cmd == Command::SavePreset {
+                    self.presets[p] = Some(self.settings.clone());
+                } else {
+                    let Some(preset) = self.presets[p].clone() else {
+                        return self.error(command, protocol::BAD_SETTINGS);
+                    };
+                    self.set(&preset);
+                    self.stored = self.settings.clone();
+                }
+                self.reply(command, &[]);
+            }
+            Command::DumpPreset | Command::RestorePreset => {
+                let p = data[0] as usize;
+                if p >= PRESETS as usize {
+                    return self.error(command, protocol::UNKNOWN_PRESET);
+                }
+                if cmd == Command::DumpPreset {
+                    match &self.presets[p] {
+                        Some(preset) => {
+                            let dump = protocol::encode_dump(preset);
+                            self.reply(command, &dump);
+                        }
+                        None => self.error(command, protocol::BAD_SETTINGS),
+                    }
+                } else {
+                    match protocol::parse_dump(&data[1..]) {
+                        Some(pairs) => {
+                            self.presets[p] = Some(pairs);
+                            self.reply(command, &[]);
+                        }
+                        None => self.error(command, protocol::BAD_SETTINGS),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for MockPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Port for MockPort {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        self.requests.push(message.to_vec());
+        if message == protocol::IDENTITY_REQUEST {
+            let [major, minor, patch] = self.version;
+            self.replies.push_back(vec![
+                0xf0,
+                0x7e,
+                0x7f,
+                0x06,
+                0x02,
+                protocol::MANUFACTURER_ID,
+                protocol::DEVICE_ID,
+                0,
+                1,
+                0,
+                major,
+                minor,
+                patch,
+                0,
+                0xf7,
+            ]);
+        } else if let Some((command, data)) = protocol::parse(message) {
+            self.handle(command, &data);
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, _timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.replies.pop_front())
+    }
+}
@@ -0,0 +1,260 @@
+//! Names for the param ids in `src/settings.rs`, so setup files can be read and written by hand
+
+/// params with a single value, where the id is the position in the list
+const GLOBAL: [&str; 32] = [
+    "root",
+    "scale",
+    "velocity",
+    "brightness",
+    "channel",
+    "zone_count",
+    "bpm",
+    "arp_enabled",
+    "arp_latch",
+    "arp_mode",
+    "arp_rate",
+    "arp_octaves",
+    "arp_gate",
+    "clock_output",
+    "clock_sync",
+    "repeat_enabled",
+    "repeat_rate",
+    "harmony_above",
+    "harmony_below",
+    "sustain_cc",
+    "mono_enabled",
+    "mono_priority",
+    "mono_legato",
+    "tilt_enabled",
+    "tilt_smoothing",
+    "tilt_deadzone",
+    "tilt_range",
+    "strike_enabled",
+    "velocity_source",
+    "velocity_curve",
+    "velocity_spread",
+    "host_channel",
+];
+
+/// more params with a single value, numbered from `LATE_GLOBAL_ID` since the ids before the groups ran out
+const LATE_GLOBAL: [&str; 2] = ["latch", "seq_length"];
+const LATE_GLOBAL_ID: u16 = 0x100;
+
+/// A group of params repeated for every drum lane, zone, fader...
+struct Indexed {
+    first_id: u16,
+    count: u16,
+    /// names of the params of each index, in id order
+    names: &'static [&'static str],
+}
+
+const INDEXED: [Indexed; 8] = [
+    Indexed {
+        first_id: 0x20,
+        count: 8,
+        names: &["drum_note", "drum_channel"],
+    },
+    Indexed {
+        first_id: 0x30,
+        count: 4,
+        names: &[
+            "euclid_steps",
+            "euclid_pulses",
+            "euclid_rotation",
+            "euclid_degree",
+        ],
+    },
+    Indexed {
+        first_id: 0x40,
+        count: 4,
+        names: &["zone_start", "zone_keyboard", "zone_octave", "zone_channel"],
+    },
+    Indexed {
+        first_id: 0x50,
+        count: 7,
+        names: &["fader_cc", "fader_channel"],
+    },
+    Indexed {
+        first_id: 0x60,
+        count: 2,
+        names: &["tilt_target", "tilt_cc", "tilt_center"],
+    },
+    Indexed {
+        first_id: 0x80,
+        count: 28,
+        names: &["strike_gain"],
+    },
+    // pattern * 8 + lane
+    Indexed {
+        first_id: 0xa0,
+        count: 32,
+        names: &["drum_steps"],
+    },
+    Indexed {
+        first_id: 0x200,
+        count: 64,
+        names: &["seq_note", "seq_levels"],
+    },
+];
+
+const NOTES: [&str; 12] = [
+    "c", "c#", "d", "d#", "e", "f", "f#", "g", "g#", "a", "a#", "b",
+];
+const SCALES: [&str; 7] = [
+    "ionian",
+    "dorian",
+    "phrygian",
+    "lydian",
+    "mixolydian",
+    "aeolian",
+    "locrian",
+];
+const KEYBOARDS: [&str; 5] = ["scale", "chords", "sampler", "bass", "waffletone"];
+
+/// Returns every param id the device knows about, in the order it stores them
+pub fn all() -> impl Iterator<Item = u16> {
+    (0..GLOBAL.len() as u16)
+        .chain(LATE_GLOBAL_ID..LATE_GLOBAL_ID + LATE_GLOBAL.len() as u16)
+        .chain(INDEXED.iter().flat_map(|group| {
+            group.first_id..group.first_id + group.count * group.names.len() as u16
+        }))
+}
+
+/// Returns the name of a param, like `root` or `zone_keyboard.1`
+pub fn name(id: u16) -> Option<String> {
+    if let Some(name) = GLOBAL.get(id as usize) {
+        return Some(name.to_string());
+    }
+    if let Some(name) = id
+        .checked_sub(LATE_GLOBAL_ID)
+        .and_then(|i| LATE_GLOBAL.get(i as usize))
+    {
+        return Some(name.to_string());
+    }
+    let group = INDEXED
+        .iter()
+        .find(|g| (g.first_id..g.first_id + g.count * g.names.len() as u16).contains(&id))?;
+    let offset = (id - group.first_id) as usize;
+    let per_index = group.names.len();
+    Some(format!(
+        "{}.{}",
+        group.names[offset % per_index],
+        offset / per_index
+    ))
+}
+
+/// Returns the id of a param from its name, the inverse of `name`
+pub fn id(name: &str) -> Option<u16> {
+    if let Some(id) = GLOBAL.iter().position(|&n| n == name) {
+        return Some(id as u16);
+    }
+    if let Some(i) = LATE_GLOBAL.iter().position(|&n| n == name) {
+        return Some(LATE_GLOBAL_ID + i as u16);
+    }
+    let (base, index) = name.split_once('.')?;
+    let index: u16 = index.parse().ok()?;
+    INDEXED.iter().find_map(|group| {
+        let offset = group.names.iter().position(|&n| n == base)? as u16;
+        (index < group.count).then(|| group.first_id + index * group.names.len() as u16 + offset)
+    })
+}
+
+/// Names the values of params that pick from a list, so files can say `scale dorian`
+fn value_names(id: u16) -> Option<&'static [&'static str]> {
+    match base_name(id)?.as_str() {
+        "root" => Some(&NOTES),
+        "scale" => Some(&SCALES),
+        "zone_keyboard" => Some(&KEYBOARDS),
+        _ => None,
+    }
+}
+
+/// Returns the name of a param without its index, like `zone_keyboard`
+fn base_name(id: u16) -> Option<String> {
+    let name = name(id)?;
+    Some(name.split('.').next()?.to_string())
+}
+
+/// the device stores the tempo as beats per minute above this
+const MIN_BPM: u16 = 30;
+
+/// Returns what the stored value of a param is offset by, so files show the real value
+fn offset(id: u16) -> u16 {
+    if name(id).as_deref() == Some("bpm") {
+        MIN_BPM
+    } else {
+        0
+    }
+}
+
+/// Reads a value for a param, either as a number or by name
+pub fn parse_value(id: u16, value: &str) -> Option<u8> {
+    match base_name(id).as_deref() {
+        Some("seq_note") => return parse_step_note(value),
+        Some("seq_levels") => return parse_step_levels(value),
+        // the resting position of an axis is a signed byte
+        Some("tilt_center") => return value.parse::<i8>().ok().map(|v| v as u8),
+        _ => {}
+    }
+    if let Ok(n) = value.parse::<u16>() {
+        return n.checked_sub(offset(id))?.try_into().ok();
+    }
+    let value = value.to_lowercase();
+    let names = value_names(id)?;
+    // the usual names for the two most common modes
+    let value = match (names == SCALES, value.as_str()) {
+        (true, "major") => "ionian",
+        (true, "minor") => "aeolian",
+        _ => value.as_str(),
+    };
+    names.iter().position(|&n| n == value).map(|i| i as u8)
+}
+
+/// Formats the value of a param, by name when it has one
+pub fn format_value(id: u16, value: u8) -> String {
+    match base_name(id).as_deref() {
+        Some("seq_note") => return format_step_note(value),
+        Some("seq_levels") => return format_step_levels(value),
+        Some("tilt_center") => return (value as i8).to_string(),
+        _ => {}
+    }
+    value_names(id)
+        .and_then(|names| names.get(value as usize))
+        .map_or_else(
+            || (value as u16 + offset(id)).to_string(),
+            |name| name.to_string(),
+        )
+}
+
+/// Sequencer notes are shown as the scale degree, or `rest`. The device stores the degree plus one
+fn parse_step_note(value: &str) -> Option<u8> {
+    if value.eq_ignore_ascii_case("rest") {
+        return Some(0);
+    }
+    value.parse::<u8>().ok()?.checked_add(1)
+}
+
+fn format_step_note(value: u8) -> String {
+    match value.checked_sub(1) {
+        Some(degree) => degree.to_string(),
+        None => "rest".to_string(),
+    }
+}
+
+/// Levels of a sequencer step are shown as `velocity/gate/probability`, each from 1 to 4.
+/// The device stores them in two bits each counting from 0
+fn parse_step_levels(value: &str) -> Option<u8> {
+    let levels: Option<Vec<u8>> = value
+        .split('/')
+        .map(|l| l.trim().parse().ok().filter(|l| (1..=4).contains(l)))
+        .collect();
+    let [velocity, gate, probability] = levels?[..] else {
+        return None;
+    };
+    Some((velocity - 1) | (gate - 1) << 2 | (probability - 1) << 4)
+}
+
+fn format_step_levels(value: u8) -> String {
+    let level = |shift: u8| (value >> shift & 3) + 1;
+    format!("{}/{}/{}", level(0), level(2), level(4))
+}
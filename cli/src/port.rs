@@ -0,0 +1,100 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    process::Command,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// Somewhere sysex messages can be sent to and received from
+pub trait Port {
+    /// Sends a whole message, from `0xf0` to `0xf7`
+    fn send(&mut self, message: &[u8]) -> io::Result<()>;
+    /// Waits for the next sysex message, returning `None` if none arrives in time
+    fn receive(&mut self, timeout: Duration) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// flag `stty` takes the terminal to set up with
+#[cfg(target_os = "linux")]
+const STTY_FILE: &str = "-F";
+#[cfg(not(target_os = "linux"))]
+const STTY_FILE: &str = "-f";
+
+/// A raw MIDI device file, like `/dev/snd/midiC1D0` on linux, or the matriu's serial port
+pub struct RawPort {
+    file: File,
+    /// messages put together by the reading thread
+    messages: Receiver<Vec<u8>>,
+}
+
+impl RawPort {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut reader = file.try_clone()?;
+
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            let mut message = Vec::new();
+            let mut buf = [0; 256];
+            while let Ok(len @ 1..) = reader.read(&mut buf) {
+                for &byte in &buf[..len] {
+                    match byte {
+                        0xf0 => message = vec![byte],
+                        // realtime messages can show up in the middle of sysex
+                        0xf8.. => {}
+                        0xf7 if !message.is_empty() => {
+                            message.push(byte);
+                            if sender.send(std::mem::take(&mut message)).is_err() {
+                                return;
+                            }
+                        }
+                        // any other status byte ends the sysex without finishing it
+                        0x80.. => message.clear(),
+                        _ if !message.is_empty() => message.push(byte),
+                        _ => {}
+                    }
+                }
+            }
+        });
+
+        Ok(Self { file, messages })
+    }
+
+    /// Opens the matriu's serial port, like `/dev/ttyACM0`, which answers sysex next to its text console.
+    /// the terminal is switched to raw mode, so bytes go through as they are
+    pub fn open_serial(path: impl AsRef<Path>) -> io::Result<Self> {
+        let port = Self::open(&path)?;
+        let status = Command::new("stty")
+            .arg(STTY_FILE)
+            .arg(path.as_ref())
+            .args(["raw", "-echo"])
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "couldn't set up {} with stty",
+                path.as_ref().display()
+            )));
+        }
+        Ok(port)
+    }
+}
+
+impl Port for RawPort {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        self.file.write_all(message)?;
+        self.file.flush()
+    }
+
+    fn receive(&mut self, timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+        match self.messages.recv_timeout(timeout) {
+            Ok(message) => Ok(Some(message)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the port was closed",
+            )),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Message layout of the sysex protocol. Keep in sync with `src/sysex.rs` and `src/settings.rs` in the firmware
+
+use std::fmt;
+
+pub const MANUFACTURER_ID: u8 = 0x7d;
+pub const DEVICE_ID: u8 = 0x4d;
+pub const PROTOCOL_VERSION: u8 = 2;
+/// set on the command of every reply
+pub const REPLY: u8 = 0x40;
+/// command of the reply to a request that failed
+pub const ERROR: u8 = 0x7f;
+/// number of presets the device stores
+pub const PRESETS: u8 = 8;
+
+/// first two bytes of a settings dump
+pub const SETTINGS_MAGIC: u8 = 0xa5;
+pub const SETTINGS_VERSION: u8 = 2;
+
+pub const IDENTITY_REQUEST: [u8; 6] = [0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Version = 0x01,
+    Get = 0x02,
+    Set = 0x03,
+    Dump = 0x04,
+    Restore = 0x05,
+    Save = 0x06,
+    SavePreset = 0x07,
+    LoadPreset = 0x08,
+    DumpPreset = 0x09,
+    RestorePreset = 0x0a,
+}
+
+impl Command {
+    pub fn from_id(id: u8) -> Option<Self> {
+        Some(match id {
+            0x01 => Self::Version,
+            0x02 => Self::Get,
+            0x03 => Self::Set,
+            0x04 => Self::Dump,
+            0x05 => Self::Restore,
+            0x06 => Self::Save,
+            0x07 => Self::SavePreset,
+            0x08 => Self::LoadPreset,
+            0x09 => Self::DumpPreset,
+            0x0a => Self::RestorePreset,
+            _ => return None,
+        })
+    }
+}
+
+/// An error sent back by the device
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DeviceError {
+    /// command of the request that failed
+    pub command: u8,
+    pub code: u8,
+}
+
+pub const UNKNOWN_COMMAND: u8 = 1;
+pub const WRONG_VERSION: u8 = 2;
+pub const WRONG_LENGTH: u8 = 3;
+pub const UNKNOWN_PARAM: u8 = 4;
+pub const UNKNOWN_PRESET: u8 = 5;
+pub const BAD_SETTINGS: u8 = 6;
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.code {
+            UNKNOWN_COMMAND => "unknown command",
+            WRONG_VERSION => "wrong protocol version, the firmware might need updating",
+            WRONG_LENGTH => "wrong length",
+            UNKNOWN_PARAM => "unknown param",
+            UNKNOWN_PRESET => "unknown preset",
+            BAD_SETTINGS => "no settings there",
+            _ => "unknown error",
+        };
+        write!(f, "command {:02x} failed: {reason}", self.command)
+    }
+}
+
+/// Builds a message, splitting every data byte into two nibbles so it fits in 7 bits
+pub fn message(command: u8, data: &[u8]) -> Vec<u8> {
+    let mut message = vec![0xf0, MANUFACTURER_ID, DEVICE_ID, PROTOCOL_VERSION, command];
+    for &byte in data {
+        message.push(byte >> 4);
+        message.push(byte & 0x0f);
+    }
+    message.push(0xf7);
+    message
+}
+
+/// Splits one of our messages into its command and data.
+/// Returns `None` for any other sysex, or if it's malformed
+pub fn parse(message: &[u8]) -> Option<(u8, Vec<u8>)> {
+    let [0xf0, MANUFACTURER_ID, DEVICE_ID, PROTOCOL_VERSION, command, ref data @ .., 0xf7] =
+        *message
+    else {
+        return None;
+    };
+    if data.len() % 2 != 0 || data.iter().any(|&b| b > 0x0f) {
+        return None;
+    }
+    let data = data.chunks_exact(2).map(|n| n[0] << 4 | n[1]).collect();
+    Some((command, data))
+}
+
+/// What the device says it is in its identity reply
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    pub manufacturer: u8,
+    pub family: u16,
+    pub model: u16,
+    pub version: [u8; 4],
+}
+
+pub fn parse_identity(message: &[u8]) -> Option<Identity> {
+    let [0xf0, 0x7e, _, 0x06, 0x02, manufacturer, f0, f1, m0, m1, v0, v1, v2, v3, 0xf7] = *message
+    else {
+        return None;
+    };
+    Some(Identity {
+        manufacturer,
+        family: f0 as u16 | (f1 as u16) << 7,
+        model: m0 as u16 | (m1 as u16) << 7,
+        version: [v0, v1, v2, v3],
+    })
+}
+
+/// Reads the `(id, value)` pairs of a settings dump. Ids and the count are two bytes, high first
+pub fn parse_dump(dump: &[u8]) -> Option<Vec<(u16, u8)>> {
+    let [SETTINGS_MAGIC, SETTINGS_VERSION, count_hi, count_lo, ref pairs @ ..] = *dump else {
+        return None;
+    };
+    let count = u16::from_be_bytes([count_hi, count_lo]) as usize;
+    if pairs.len() < count * 3 {
+        return None;
+    }
+    Some(
+        pairs
+            .chunks_exact(3)
+            .take(count)
+            .map(|p| (u16::from_be_bytes([p[0], p[1]]), p[2]))
+            .collect(),
+    )
+}
+
+/// Builds a settings dump the device can restore
+pub fn encode_dump(pairs: &[(u16, u8)]) -> Vec<u8> {
+    let mut dump = vec![SETTINGS_MAGIC, SETTINGS_VERSION];
+    dump.extend((pairs.len() as u16).to_be_bytes());
+    for &(id, value) in pairs {
+        dump.extend(id.to_be_bytes());
+        dump.push(value);
+    }
+    dump
+}
@@ -0,0 +1,85 @@
+//! Setup files: plain text with a `name value` line per setting,
+//! followed by a `[preset n]` section for every stored preset.
+//!
+//! ```text
+//! # bass on the left, chords on the right
+//! root d
+//! scale dorian
+//! zone_keyboard.0 bass
+//!
+//! [preset 0]
+//! scale minor
+//! ```
+//!
+//! Settings that aren't listed are left as they are, so a file can set up just a few things
+
+use std::fmt::{self, Write};
+
+use crate::{params, protocol::PRESETS, Error, Result};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Setup {
+    /// settings in use, as `(id, value)` pairs
+    pub current: Vec<(u16, u8)>,
+    /// presets by number
+    pub presets: Vec<(u8, Vec<(u16, u8)>)>,
+}
+
+impl Setup {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut setup = Setup::default();
+        for (n, line) in text.lines().enumerate() {
+            let bad = |e: String| Error::BadFile(n + 1, e);
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let p = section
+                    .strip_prefix("preset")
+                    .and_then(|p| p.trim().parse().ok())
+                    .filter(|&p| p < PRESETS)
+                    .ok_or_else(|| bad(format!("unknown section {section}")))?;
+                setup.presets.push((p, Vec::new()));
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| bad(format!("{line} has no value")))?;
+            let id = params::id(name).ok_or_else(|| bad(format!("unknown param {name}")))?;
+            let value = params::parse_value(id, value.trim())
+                .ok_or_else(|| bad(format!("bad value {} for {name}", value.trim())))?;
+
+            let pairs = match setup.presets.last_mut() {
+                Some((_, preset)) => preset,
+                None => &mut setup.current,
+            };
+            pairs.push((id, value));
+        }
+        Ok(setup)
+    }
+}
+
+fn write_pairs(f: &mut fmt::Formatter, pairs: &[(u16, u8)]) -> fmt::Result {
+    for &(id, value) in pairs {
+        // ids the device has but we don't know about are dropped, they'd be unreadable anyway
+        if let Some(name) = params::name(id) {
+            writeln!(f, "{name} {}", params::format_value(id, value))?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Setup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_pairs(f, &self.current)?;
+        for (p, preset) in &self.presets {
+            f.write_char('\n')?;
+            writeln!(f, "[preset {p}]")?;
+            write_pairs(f, preset)?;
+        }
+        Ok(())
+    }
+}
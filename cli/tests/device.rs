@@ -0,0 +1,124 @@
+use matriu_cli::{params, Device, Error, MockPort, Setup};
+
+fn id(name: &str) -> u16 {
+    params::id(name).unwrap()
+}
+
+#[test]
+fn version_and_identity() {
+    let mut device = Device::new(MockPort::new());
+    assert_eq!(device.version().unwrap(), [0, 1, 0, 2]);
+    let identity = device.identity().unwrap();
+    assert_eq!(identity.manufacturer, 0x7d);
+    assert_eq!(identity.version, [0, 1, 0, 0]);
+}
+
+#[test]
+fn set_is_kept_until_saved() {
+    let mut device = Device::new(MockPort::new());
+    assert_eq!(device.set(id("scale"), 1).unwrap(), 1);
+    assert_eq!(device.get(id("scale")).unwrap(), 1);
+    assert_eq!(device.port.stored[1], (1, 0));
+
+    device.save().unwrap();
+    assert_eq!(device.port.stored[1], (1, 1));
+}
+
+#[test]
+fn device_errors() {
+    let mut device = Device::new(MockPort::new());
+    assert!(matches!(device.get(0x70), Err(Error::Device(e)) if e.code == 4));
+    assert!(matches!(device.save_preset(8), Err(Error::Device(e)) if e.code == 5));
+    assert!(!device.load_preset(0).unwrap());
+}
+
+#[test]
+fn backup_and_restore_to_another_device() {
+    let mut device = Device::new(MockPort::new());
+    device.port.set(&[(id("root"), 2), (id("zone_count"), 2)]);
+    device.save_preset(3).unwrap();
+    device
+        .port
+        .set(&[(id("root"), 7), (id("zone_keyboard.1"), 3)]);
+
+    let stored = device.port.stored.clone();
+    let setup = device.backup().unwrap();
+    // backing up reads the presets without loading them, so nothing is stored
+    assert_eq!(device.port.get(id("root")), Some(7));
+    assert_eq!(device.port.stored, stored);
+    assert_eq!(setup.presets.len(), 1);
+    assert_eq!(setup.presets[0].0, 3);
+
+    // going through the file, like backing up one unit and setting up another
+    let setup = Setup::parse(&setup.to_string()).unwrap();
+    let mut other = Device::new(MockPort::new());
+    other.restore_setup(&setup).unwrap();
+    // each preset and the current settings are written once
+    let writes = other
+        .port
+        .requests
+        .iter()
+        .filter(|r| [0x05, 0x0a].contains(&r[4]));
+    assert_eq!(writes.count(), 2);
+
+    assert_eq!(other.port.settings, device.port.settings);
+    assert_eq!(other.port.stored, device.port.settings);
+    assert_eq!(other.port.presets[3], device.port.presets[3]);
+    assert_eq!(other.port.presets[0], None);
+}
+
+#[test]
+fn presets_in_files_start_from_the_settings_in_use() {
+    let mut device = Device::new(MockPort::new());
+    device.port.set(&[(id("velocity"), 100), (id("scale"), 2)]);
+
+    let setup = Setup::parse("root e\n[preset 1]\nscale minor\n").unwrap();
+    device.restore_setup(&setup).unwrap();
+
+    let preset = device.port.presets[1].as_ref().unwrap();
+    assert_eq!(preset.len(), device.port.settings.len());
+    assert!(preset.contains(&(id("scale"), 5)));
+    assert!(preset.contains(&(id("velocity"), 100)));
+    // the preset is stored without being loaded
+    assert_eq!(device.port.get(id("scale")), Some(2));
+    assert_eq!(device.port.get(id("root")), Some(4));
+}
+
+#[test]
+fn push_only_sets_whats_listed() {
+    let mut device = Device::new(MockPort::new());
+    device.port.set(&[(id("velocity"), 100)]);
+
+    let setup = Setup::parse("scale minor\nzone_count 2\nzone_start.1 4\nzone_keyboard.1 chords\n");
+    device.restore(&setup.unwrap().current).unwrap();
+
+    assert_eq!(device.port.get(id("scale")), Some(5));
+    assert_eq!(device.port.get(id("zone_keyboard.1")), Some(1));
+    assert_eq!(device.port.get(id("velocity")), Some(100));
+    assert_eq!(device.port.stored, device.port.settings);
+}
+
+#[test]
+fn status() {
+    let mut device = Device::new(MockPort::new());
+    device.port.set(&[
+        (id("root"), 2),
+        (id("scale"), 1),
+        (id("bpm"), 90),
+        (id("zone_count"), 2),
+        (id("zone_start.0"), 1),
+        (id("zone_start.1"), 5),
+        (id("zone_keyboard.1"), 3),
+        (id("zone_channel.1"), 16),
+    ]);
+    let status = device.status().unwrap();
+    assert_eq!(
+        status,
+        "matriu 0.1.0, protocol 2\n\
+         root d dorian\n\
+         channel 1\n\
+         bpm 120\n\
+         zone 1 from column 1: scale, octave 0, channel 1\n\
+         zone 2 from column 5: bass, octave 0, channel global\n"
+    );
+}
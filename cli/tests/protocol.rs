@@ -0,0 +1,113 @@
+use matriu_cli::{
+    params,
+    protocol::{self, encode_dump, parse_dump, parse_identity},
+};
+
+#[test]
+fn data_is_split_into_nibbles() {
+    let message = protocol::message(0x03, &[0x80, 0x2a]);
+    assert_eq!(
+        message,
+        [0xf0, 0x7d, 0x4d, 0x02, 0x03, 0x08, 0x00, 0x02, 0x0a, 0xf7]
+    );
+    assert!(message[1..message.len() - 1].iter().all(|&b| b < 0x80));
+    assert_eq!(protocol::parse(&message), Some((0x03, vec![0x80, 0x2a])));
+}
+
+#[test]
+fn other_sysex_is_ignored() {
+    // another manufacturer
+    assert_eq!(protocol::parse(&[0xf0, 0x41, 0x4d, 0x01, 0x01, 0xf7]), None);
+    // a newer protocol version
+    assert_eq!(protocol::parse(&[0xf0, 0x7d, 0x4d, 0x03, 0x01, 0xf7]), None);
+    // half a byte
+    assert_eq!(
+        protocol::parse(&[0xf0, 0x7d, 0x4d, 0x02, 0x02, 0x01, 0xf7]),
+        None
+    );
+}
+
+#[test]
+fn dumps_round_trip() {
+    let pairs = [(0, 2), (1, 5), (0x41, 3), (0x213, 9)];
+    let dump = encode_dump(&pairs);
+    assert_eq!(&dump[..4], &[0xa5, 0x02, 0, 4]);
+    assert_eq!(&dump[dump.len() - 3..], &[0x02, 0x13, 9]);
+    assert_eq!(parse_dump(&dump), Some(pairs.to_vec()));
+    assert_eq!(parse_dump(&[0xa5, 0x01, 0, 0]), None);
+    assert_eq!(parse_dump(&[0xa5, 0x02, 0, 2, 0, 0, 1]), None);
+}
+
+#[test]
+fn identity_reply() {
+    let reply = [
+        0xf0, 0x7e, 0x7f, 0x06, 0x02, 0x7d, 0x4d, 0x00, 0x01, 0x00, 0, 1, 0, 0, 0xf7,
+    ];
+    let identity = parse_identity(&reply).unwrap();
+    assert_eq!(identity.manufacturer, 0x7d);
+    assert_eq!(identity.family, 0x4d);
+    assert_eq!(identity.model, 1);
+    assert_eq!(identity.version, [0, 1, 0, 0]);
+}
+
+#[test]
+fn every_param_has_a_name() {
+    let ids: Vec<_> = params::all().collect();
+    // the same number of params the firmware stores
+    assert_eq!(ids.len(), 32 + 2 + 16 + 16 + 16 + 14 + 6 + 28 + 32 + 128);
+    for id in ids {
+        let name = params::name(id).unwrap();
+        assert_eq!(params::id(&name), Some(id), "{name}");
+    }
+    assert_eq!(params::id("zone_keyboard.1"), Some(0x45));
+    assert_eq!(params::id("strike_gain.27"), Some(0x9b));
+    assert_eq!(params::id("zone_keyboard.4"), None);
+    assert_eq!(params::id("latch"), Some(0x100));
+    assert_eq!(params::id("seq_levels.63"), Some(0x27f));
+    assert_eq!(params::name(0x70), None);
+    assert_eq!(params::name(0x102), None);
+}
+
+#[test]
+fn values_by_name() {
+    let scale = params::id("scale").unwrap();
+    assert_eq!(params::parse_value(scale, "dorian"), Some(1));
+    assert_eq!(params::parse_value(scale, "Minor"), Some(5));
+    assert_eq!(params::format_value(scale, 5), "aeolian");
+
+    let root = params::id("root").unwrap();
+    assert_eq!(params::parse_value(root, "f#"), Some(6));
+    assert_eq!(params::parse_value(root, "h"), None);
+
+    let keyboard = params::id("zone_keyboard.2").unwrap();
+    assert_eq!(params::parse_value(keyboard, "waffletone"), Some(4));
+
+    // tempo is stored above 30 bpm, but read and written as it is
+    let bpm = params::id("bpm").unwrap();
+    assert_eq!(params::parse_value(bpm, "120"), Some(90));
+    assert_eq!(params::format_value(bpm, 90), "120");
+    assert_eq!(params::parse_value(bpm, "20"), None);
+
+    let note = params::id("seq_note.3").unwrap();
+    assert_eq!(params::parse_value(note, "rest"), Some(0));
+    assert_eq!(params::parse_value(note, "4"), Some(5));
+    assert_eq!(params::format_value(note, 5), "4");
+    assert_eq!(params::format_value(note, 0), "rest");
+
+    // velocity, gate and probability, two bits each
+    let levels = params::id("seq_levels.3").unwrap();
+    assert_eq!(params::parse_value(levels, "3/2/4"), Some(0b11_01_10));
+    assert_eq!(params::format_value(levels, 0b11_01_10), "3/2/4");
+    assert_eq!(params::parse_value(levels, "3/5/4"), None);
+    assert_eq!(params::parse_value(levels, "3/2"), None);
+
+    // the resting position of a tilt axis can be below 0
+    let center = params::id("tilt_center.1").unwrap();
+    assert_eq!(params::parse_value(center, "-12"), Some(0xf4));
+    assert_eq!(params::format_value(center, 0xf4), "-12");
+    assert_eq!(params::parse_value(center, "200"), None);
+
+    let velocity = params::id("velocity").unwrap();
+    assert_eq!(params::parse_value(velocity, "100"), Some(100));
+    assert_eq!(params::parse_value(velocity, "loud"), None);
+}
@@ -0,0 +1,43 @@
+use matriu_cli::{Error, Setup};
+
+#[test]
+fn parses_settings_and_presets() {
+    let setup = Setup::parse(
+        "# a comment\n\
+         root d\n\
+         scale dorian # trailing comment\n\
+         \n\
+         [preset 2]\n\
+         bpm 120\n",
+    )
+    .unwrap();
+    assert_eq!(setup.current, [(0, 2), (1, 1)]);
+    assert_eq!(setup.presets, [(2, vec![(6, 90)])]);
+}
+
+#[test]
+fn writes_names() {
+    let setup = Setup {
+        current: vec![(0, 9), (0x41, 4)],
+        presets: vec![(0, vec![(2, 100)])],
+    };
+    let text = setup.to_string();
+    assert_eq!(
+        text,
+        "root a\nzone_keyboard.0 waffletone\n\n[preset 0]\nvelocity 100\n"
+    );
+    assert_eq!(Setup::parse(&text).unwrap(), setup);
+}
+
+#[test]
+fn reports_bad_lines() {
+    let line = |text| match Setup::parse(text) {
+        Err(Error::BadFile(line, _)) => line,
+        _ => panic!("{text} should fail"),
+    };
+    assert_eq!(line("root c\nwobble 3\n"), 2);
+    assert_eq!(line("scale\n"), 1);
+    assert_eq!(line("root q\n"), 1);
+    assert_eq!(line("root c\n[preset 9]\n"), 2);
+    assert_eq!(line("[settings]\n"), 1);
+}
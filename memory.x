@@ -1,7 +1,8 @@
 MEMORY
 {
-/* Leave 16k for the default bootloader on the NeoTrellis M4 */
-FLASH (rx) : ORIGIN = 0x00000000 + 16K, LENGTH = 512K - 16K
+/* Leave 16k for the default bootloader on the NeoTrellis M4,
+   and the last 8k block for the stored settings */
+FLASH (rx) : ORIGIN = 0x00000000 + 16K, LENGTH = 512K - 16K - 8K
 RAM (xrw)  : ORIGIN = 0x20000000, LENGTH = 192K
 }
 _stack_start = ORIGIN(RAM) + LENGTH(RAM);
@@ -0,0 +1,196 @@
+//! Turns raw accelerometer readings into controller values.
+//! Kept apart from the firmware so it can be tested on the host with recorded samples
+#![no_std]
+
+/// largest value an axis can reach, the same range as pitch bend
+pub const FULL_SCALE: i16 = 8191;
+
+/// bits of extra precision kept while smoothing
+const FRACTION: u32 = 8;
+
+/// A raw reading from the accelerometer, in counts.
+/// with the ADXL343 in full resolution mode, 1g is about 256 counts
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+/// Maps how far the board is tilted on its x and y axes to values from `-FULL_SCALE` to `FULL_SCALE`
+pub struct Tilt {
+    /// how much readings are smoothed, from 0 (not at all) to 7
+    pub smoothing: u8,
+    /// counts around the center that read as 0, so small wobbles don't send anything
+    pub deadzone: i16,
+    /// counts away from the center that read as full scale
+    pub range: i16,
+
+    /// resting position, in counts with `FRACTION` extra bits
+    center: [i32; 2],
+    /// smoothed position, in counts with `FRACTION` extra bits
+    smoothed: [i32; 2],
+    /// whether `smoothed` holds a reading yet
+    primed: bool,
+}
+
+impl Tilt {
+    pub const fn new() -> Self {
+        Self {
+            smoothing: 3,
+            deadzone: 16,
+            // about 45 degrees
+            range: 180,
+
+            center: [0; 2],
+            smoothed: [0; 2],
+            primed: false,
+        }
+    }
+
+    /// Feeds a new reading, returning the value of the x and y axes
+    pub fn update(&mut self, sample: Sample) -> [i16; 2] {
+        let raw = [(sample.x as i32) << FRACTION, (sample.y as i32) << FRACTION];
+        if !self.primed {
+            self.smoothed = raw;
+            self.primed = true;
+        }
+
+        let mut values = [0; 2];
+        for axis in 0..2 {
+            // exponential moving average, each reading moves 1/2^smoothing of the way
+            self.smoothed[axis] += (raw[axis] - self.smoothed[axis]) >> self.smoothing.min(7);
+            values[axis] = self.scale((self.smoothed[axis] - self.center[axis]) >> FRACTION);
+        }
+        values
+    }
+
+    /// Makes the current position the resting one
+    pub fn calibrate(&mut self) {
+        self.center = self.smoothed;
+    }
+
+    /// Returns the resting position, in counts
+    pub fn center(&self) -> [i16; 2] {
+        self.center.map(|c| (c >> FRACTION) as i16)
+    }
+
+    /// Sets the resting position, in counts, as returned by `center`
+    pub fn set_center(&mut self, center: [i16; 2]) {
+        self.center = center.map(|c| (c as i32) << FRACTION);
+    }
+
+    /// Forgets the smoothed position, so the next reading is taken as is
+    pub fn reset(&mut self) {
+        self.primed = false;
+    }
+
+    /// Scales `counts` away from the center, leaving out the deadzone
+    fn scale(&self, counts: i32) -> i16 {
+        let deadzone = self.deadzone.max(0) as i32;
+        let range = (self.range as i32).max(deadzone + 1);
+        let beyond = counts.abs() - deadzone;
+        if beyond <= 0 {
+            return 0;
+        }
+
+        let value = (beyond * FULL_SCALE as i32 / (range - deadzone)).min(FULL_SCALE as i32);
+        (value * counts.signum()) as i16
+    }
+}
+
+impl Default for Tilt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// number of readings a strike is looked for in, about 20ms when read every 5ms
+pub const STRIKE_WINDOW: usize = 4;
+/// shocks below this are sensor noise, not a pad being hit
+pub const STRIKE_NOISE: u16 = 12;
+/// gain that leaves a shock as is
+pub const UNITY_GAIN: u8 = 64;
+/// velocity a calibration strike is mapped to, so there's room above it for harder hits
+pub const CALIBRATION_VELOCITY: u8 = 96;
+
+/// Measures the shock of pads being hit, as how far readings jump away from the resting acceleration
+pub struct Strike {
+    /// slowly moving average of the readings, in counts with `FRACTION` extra bits
+    baseline: [i32; 3],
+    /// shock of the last `STRIKE_WINDOW` readings
+    shocks: [u16; STRIKE_WINDOW],
+    next: usize,
+    primed: bool,
+}
+
+impl Strike {
+    pub const fn new() -> Self {
+        Self {
+            baseline: [0; 3],
+            shocks: [0; STRIKE_WINDOW],
+            next: 0,
+            primed: false,
+        }
+    }
+
+    /// Feeds a new reading
+    pub fn update(&mut self, sample: Sample) {
+        let raw = [sample.x, sample.y, sample.z].map(|v| (v as i32) << FRACTION);
+        if !self.primed {
+            self.baseline = raw;
+            self.primed = true;
+        }
+
+        let mut shock = 0;
+        for (raw, baseline) in raw.into_iter().zip(&mut self.baseline) {
+            shock += ((raw - *baseline) >> FRACTION).abs();
+            // follows tilting, but too slowly to follow a hit
+            *baseline += (raw - *baseline) >> 4;
+        }
+        self.shocks[self.next] = shock.min(u16::MAX as i32) as u16;
+        self.next = (self.next + 1) % STRIKE_WINDOW;
+    }
+
+    /// Returns the biggest shock in the last few readings
+    pub fn peak(&self) -> u16 {
+        self.shocks.iter().copied().max().unwrap_or(0)
+    }
+}
+
+impl Default for Strike {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a shock into a velocity, scaled by the `gain` of the pad that was hit.
+/// Returns `None` if the shock can't be told apart from noise, so a fixed velocity can be used instead
+pub fn strike_velocity(shock: u16, gain: u8) -> Option<u8> {
+    if shock < STRIKE_NOISE {
+        return None;
+    }
+    let velocity = shock as u32 * gain as u32 / UNITY_GAIN as u32;
+    Some(velocity.clamp(1, 127) as u8)
+}
+
+/// Returns the gain that turns `shock` into `CALIBRATION_VELOCITY`.
+/// pads further from the sensor shake it less, so they get a bigger gain
+pub fn strike_gain(shock: u16) -> Option<u8> {
+    if shock < STRIKE_NOISE {
+        return None;
+    }
+    let gain = CALIBRATION_VELOCITY as u32 * UNITY_GAIN as u32 / shock as u32;
+    Some(gain.clamp(1, u8::MAX as u32) as u8)
+}
+
+/// Converts an axis value to a pitch bend, from -8192 to 8191 with 0 in the center
+pub fn pitch_bend(value: i16) -> i16 {
+    value.clamp(-FULL_SCALE - 1, FULL_SCALE)
+}
+
+/// Converts an axis value to a CC value. only tilting towards the positive side raises it,
+/// so a resting board sends 0, like a mod wheel at rest
+pub fn cc(value: i16) -> u8 {
+    (value.clamp(0, FULL_SCALE) as i32 * 127 / FULL_SCALE as i32) as u8
+}
@@ -0,0 +1,80 @@
+use motion::{
+    strike_gain, strike_velocity, Sample, Strike, CALIBRATION_VELOCITY, STRIKE_WINDOW, UNITY_GAIN,
+};
+
+/// the board lying flat while nothing is pressed
+const IDLE: [(i16, i16, i16); 6] = [
+    (3, -5, 258),
+    (4, -6, 255),
+    (2, -4, 257),
+    (5, -5, 256),
+    (3, -7, 258),
+    (4, -5, 254),
+];
+
+/// a pad near the sensor hit softly, then the board settling
+const SOFT_HIT: [(i16, i16, i16); 4] = [(6, -2, 279), (2, -8, 247), (4, -5, 259), (3, -5, 256)];
+
+/// the same pad hit hard
+const HARD_HIT: [(i16, i16, i16); 4] = [(15, 9, 331), (-6, -19, 214), (7, -2, 266), (4, -5, 257)];
+
+fn feed(strike: &mut Strike, samples: &[(i16, i16, i16)]) {
+    for &(x, y, z) in samples {
+        strike.update(Sample { x, y, z });
+    }
+}
+
+fn shock_of(hit: &[(i16, i16, i16)]) -> u16 {
+    let mut strike = Strike::new();
+    feed(&mut strike, &IDLE);
+    feed(&mut strike, &hit[..1]);
+    strike.peak()
+}
+
+#[test]
+fn idle_board_is_noise() {
+    let mut strike = Strike::new();
+    feed(&mut strike, &IDLE);
+    assert_eq!(strike_velocity(strike.peak(), UNITY_GAIN), None);
+}
+
+#[test]
+fn harder_hits_are_louder() {
+    let soft = strike_velocity(shock_of(&SOFT_HIT), UNITY_GAIN).unwrap();
+    let hard = strike_velocity(shock_of(&HARD_HIT), UNITY_GAIN).unwrap();
+    assert!(soft < hard, "soft was {soft}, hard was {hard}");
+}
+
+#[test]
+fn peak_is_forgotten_after_the_window() {
+    let mut strike = Strike::new();
+    feed(&mut strike, &IDLE);
+    feed(&mut strike, &HARD_HIT);
+    assert!(strike.peak() > 50);
+    for _ in 0..STRIKE_WINDOW {
+        feed(&mut strike, &IDLE[..1]);
+    }
+    assert_eq!(strike_velocity(strike.peak(), UNITY_GAIN), None);
+}
+
+#[test]
+fn calibration_maps_the_strike_to_the_reference_velocity() {
+    let shock = shock_of(&SOFT_HIT);
+    let gain = strike_gain(shock).unwrap();
+    let velocity = strike_velocity(shock, gain).unwrap();
+    assert!(
+        velocity.abs_diff(CALIBRATION_VELOCITY) <= 4,
+        "velocity was {velocity}"
+    );
+}
+
+#[test]
+fn calibrating_on_noise_does_nothing() {
+    assert_eq!(strike_gain(3), None);
+}
+
+#[test]
+fn velocity_is_clamped() {
+    assert_eq!(strike_velocity(u16::MAX, u8::MAX), Some(127));
+    assert_eq!(strike_velocity(20, 1), Some(1));
+}
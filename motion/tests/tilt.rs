@@ -0,0 +1,123 @@
+use motion::{cc, pitch_bend, Sample, Tilt, FULL_SCALE};
+
+/// the board lying flat on a desk, with sensor noise
+const FLAT: [(i16, i16, i16); 8] = [
+    (3, -5, 258),
+    (4, -6, 255),
+    (2, -4, 257),
+    (5, -5, 256),
+    (3, -7, 258),
+    (4, -5, 254),
+    (2, -6, 257),
+    (4, -4, 256),
+];
+
+/// the board tilted about 30 degrees to the right
+const TILTED_RIGHT: [(i16, i16, i16); 4] = [
+    (131, -4, 222),
+    (129, -6, 221),
+    (130, -5, 223),
+    (128, -5, 222),
+];
+
+/// the board tilted all the way forward
+const TILTED_FORWARD: [(i16, i16, i16); 4] =
+    [(4, 251, 12), (3, 254, 9), (5, 252, 11), (4, 253, 10)];
+
+fn feed(tilt: &mut Tilt, samples: &[(i16, i16, i16)]) -> [i16; 2] {
+    let mut values = [0; 2];
+    for &(x, y, z) in samples {
+        values = tilt.update(Sample { x, y, z });
+    }
+    values
+}
+
+fn calibrated() -> Tilt {
+    let mut tilt = Tilt::new();
+    feed(&mut tilt, &FLAT);
+    tilt.calibrate();
+    tilt
+}
+
+#[test]
+fn resting_board_reads_zero() {
+    let mut tilt = calibrated();
+    assert_eq!(feed(&mut tilt, &FLAT), [0, 0]);
+}
+
+#[test]
+fn noise_stays_inside_the_deadzone() {
+    let mut tilt = calibrated();
+    for &(x, y, z) in &FLAT {
+        assert_eq!(
+            tilt.update(Sample {
+                x: x + 6,
+                y: y - 6,
+                z
+            }),
+            [0, 0]
+        );
+    }
+}
+
+#[test]
+fn tilting_right_raises_x_only() {
+    let mut tilt = calibrated();
+    for _ in 0..8 {
+        feed(&mut tilt, &TILTED_RIGHT);
+    }
+    let [x, y] = feed(&mut tilt, &TILTED_RIGHT);
+    assert!(x > FULL_SCALE / 2 && x < FULL_SCALE, "x was {x}");
+    assert_eq!(y, 0);
+}
+
+#[test]
+fn tilting_past_the_range_is_clamped() {
+    let mut tilt = calibrated();
+    for _ in 0..8 {
+        feed(&mut tilt, &TILTED_FORWARD);
+    }
+    assert_eq!(feed(&mut tilt, &TILTED_FORWARD), [0, FULL_SCALE]);
+}
+
+#[test]
+fn smoothing_eases_into_sudden_moves() {
+    let mut tilt = calibrated();
+    let first = feed(&mut tilt, &TILTED_RIGHT[..2])[0];
+    let later = feed(&mut tilt, &TILTED_RIGHT)[0];
+    assert!(first > 0 && first < later, "went from {first} to {later}");
+
+    let mut raw = calibrated();
+    raw.smoothing = 0;
+    assert!(feed(&mut raw, &TILTED_RIGHT[..2])[0] > first);
+}
+
+#[test]
+fn calibrating_while_tilted_moves_the_center() {
+    let mut tilt = Tilt::new();
+    for _ in 0..8 {
+        feed(&mut tilt, &TILTED_RIGHT);
+    }
+    tilt.calibrate();
+    assert_eq!(feed(&mut tilt, &TILTED_RIGHT), [0, 0]);
+    assert!(feed(&mut tilt, &FLAT)[0] < 0);
+}
+
+#[test]
+fn converts_to_midi_ranges() {
+    assert_eq!(pitch_bend(0), 0);
+    assert_eq!(pitch_bend(FULL_SCALE), 8191);
+    assert_eq!(pitch_bend(-FULL_SCALE), -8191);
+    assert_eq!(cc(0), 0);
+    assert_eq!(cc(-FULL_SCALE), 0);
+    assert_eq!(cc(FULL_SCALE), 127);
+    assert_eq!(cc(FULL_SCALE / 2), 63);
+}
+
+#[test]
+fn center_survives_a_round_trip() {
+    let tilt = calibrated();
+    let mut restored = Tilt::new();
+    restored.set_center(tilt.center());
+    assert_eq!(feed(&mut restored, &FLAT), [0, 0]);
+}
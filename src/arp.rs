@@ -0,0 +1,208 @@
+use crate::state::State;
+
+/// max number of held notes the arpeggiator keeps track of
+pub const MAX_ARP_NOTES: usize = 16;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ArpMode {
+    Up,
+    Down,
+    UpDown,
+    Random,
+    AsPlayed,
+    /// plays every held note at once on each step
+    ChordRepeat,
+}
+
+impl ArpMode {
+    pub const fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Up,
+            1 => Self::Down,
+            2 => Self::UpDown,
+            3 => Self::Random,
+            4 => Self::AsPlayed,
+            5 => Self::ChordRepeat,
+            _ => panic!("number is not in 0..6"),
+        }
+    }
+}
+
+/// Step lengths, in clock ticks: 1/4, 1/8, 1/8T, 1/16, 1/16T, 1/32
+pub const ARP_RATES: [u32; 6] = [24, 12, 8, 6, 4, 3];
+
+pub struct Arp {
+    pub enabled: bool,
+    pub latch: bool,
+    pub mode: ArpMode,
+    /// index into `ARP_RATES`
+    pub rate: u8,
+    /// number of octaves the pattern spans, from 1 to 4
+    pub octaves: u8,
+    /// how long notes last, in quarters of a step, from 1 to 4
+    pub gate: u8,
+
+    /// held notes as `(channel, note, velocity)`, in the order they were played
+    notes: [(u8, u8, u8); MAX_ARP_NOTES],
+    len: usize,
+    /// number of notes physically held, used by latch
+    held: usize,
+
+    step: usize,
+    /// ticks since the last step
+    ticks: u32,
+    /// notes currently sounding, to be turned off when the gate ends
+    playing: [(u8, u8); MAX_ARP_NOTES],
+    playing_len: usize,
+}
+
+impl Arp {
+    pub const fn new() -> Self {
+        Self {
+            enabled: false,
+            latch: false,
+            mode: ArpMode::Up,
+            rate: 3,
+            octaves: 1,
+            gate: 2,
+
+            notes: [(0, 0, 0); MAX_ARP_NOTES],
+            len: 0,
+            held: 0,
+
+            step: 0,
+            ticks: 0,
+            playing: [(0, 0); MAX_ARP_NOTES],
+            playing_len: 0,
+        }
+    }
+
+    /// Forgets every held note. Notes that are still sounding are turned off on the next update
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.held = 0;
+        self.step = 0;
+    }
+
+    /// Adds or removes a held note. Steps play it at the `velocity` it was played with
+    pub fn note(&mut self, channel: u8, note: u8, velocity: u8, on: bool) {
+        let pos = self.notes[..self.len]
+            .iter()
+            .position(|&(c, n, _)| (c, n) == (channel, note));
+
+        if on {
+            // with latch, a new chord replaces the old one once every key was released
+            if self.latch && self.held == 0 {
+                self.len = 0;
+                self.step = 0;
+            }
+            self.held += 1;
+
+            if pos.is_none() && self.len < MAX_ARP_NOTES {
+                self.notes[self.len] = (channel, note, velocity);
+                self.len += 1;
+            }
+        } else {
+            self.held = self.held.saturating_sub(1);
+
+            if let (Some(pos), false) = (pos, self.latch) {
+                self.notes.copy_within(pos + 1..self.len, pos);
+                self.len -= 1;
+            }
+        }
+    }
+
+    /// Returns the held notes sorted by pitch
+    fn sorted(&self) -> [(u8, u8, u8); MAX_ARP_NOTES] {
+        let mut sorted = self.notes;
+        sorted[..self.len].sort_unstable_by_key(|&(_, note, _)| note);
+        sorted
+    }
+
+    /// Returns the note at `i` in the pattern, counting every octave, as `(channel, note, velocity)`
+    fn pattern_note(&self, i: usize, random: u32) -> (u8, u8, u8) {
+        let steps = self.len * self.octaves as usize;
+        let i = match self.mode {
+            ArpMode::Up | ArpMode::AsPlayed | ArpMode::ChordRepeat => i % steps,
+            ArpMode::Down => steps - 1 - i % steps,
+            ArpMode::UpDown if steps > 1 => {
+                let i = i % (2 * steps - 2);
+                if i < steps {
+                    i
+                } else {
+                    2 * steps - 2 - i
+                }
+            }
+            ArpMode::UpDown => 0,
+            ArpMode::Random => random as usize % steps,
+        };
+
+        let notes = if self.mode == ArpMode::AsPlayed {
+            self.notes
+        } else {
+            self.sorted()
+        };
+        let (channel, note, velocity) = notes[i % self.len];
+        (channel, note + 12 * (i / self.len) as u8, velocity)
+    }
+}
+
+impl State {
+    /// Runs the arpeggiator for `ticks` clock ticks
+    pub fn update_arp(&mut self, ticks: u32) {
+        if !self.arp.enabled || self.arp.len == 0 {
+            self.arp_notes_off();
+            self.arp.ticks = 0;
+            return;
+        }
+
+        let rate = ARP_RATES[self.arp.rate as usize];
+        let was_running = self.arp.playing_len > 0 || self.arp.ticks > 0;
+        self.arp.ticks += ticks;
+
+        let gate = (rate * self.arp.gate as u32 / 4).max(1);
+        if self.arp.ticks >= gate {
+            self.arp_notes_off();
+        }
+
+        // the first step plays right away, the rest once a step has passed
+        if was_running && self.arp.ticks < rate {
+            return;
+        }
+        self.arp.ticks = if was_running {
+            self.arp.ticks - rate
+        } else {
+            0
+        };
+        self.arp_notes_off();
+
+        let mut notes = [(0, 0, 0); MAX_ARP_NOTES];
+        let count = if self.arp.mode == ArpMode::ChordRepeat {
+            let octave = (self.arp.step % self.arp.octaves as usize) as u8;
+            for (i, &(channel, note, velocity)) in self.arp.notes[..self.arp.len].iter().enumerate()
+            {
+                notes[i] = (channel, note + 12 * octave, velocity);
+            }
+            self.arp.len
+        } else {
+            let random = self.random();
+            notes[0] = self.arp.pattern_note(self.arp.step, random);
+            1
+        };
+
+        for (i, &(channel, note, velocity)) in notes[..count].iter().enumerate() {
+            self.send_note(channel, note, velocity, true);
+            self.arp.playing[i] = (channel, note);
+        }
+        self.arp.playing_len = count;
+        self.arp.step = self.arp.step.wrapping_add(1);
+    }
+
+    fn arp_notes_off(&mut self) {
+        for i in 0..self.arp.playing_len {
+            let (channel, note) = self.arp.playing[i];
+            self.send_midi(channel, note, false);
+        }
+        self.arp.playing_len = 0;
+    }
+}
@@ -2,18 +2,35 @@ use trellis_m4 as bsp;
 use ws2812_timer_delay as ws2812;
 
 use bsp::{
+    adxl343::{accelerometer::RawAccelerometer, Adxl343},
     gpio::{v2::PA27, Output, Pin, PushPull},
     hal::{
         clock::GenericClockController,
         delay::Delay,
         ehal::digital::v1_compat::OldOutputPin,
+        nvm::{EraseGranularity, Nvm},
         pac::{CorePeripherals, Peripherals},
         timer::SpinTimer,
     },
     Keypad,
 };
 
+use cortex_m::peripheral::DWT;
+use motion::Sample;
+
+use crate::settings::{PRESETS, SETTINGS_LEN};
+
+/// `with_internal_32kosc` runs the cpu at 120MHz
+const CYCLES_PER_MICRO: u64 = 120;
+
+/// Settings live in the last flash block, which `memory.x` keeps out of the program
+const SETTINGS_ADDRESS: usize = 512 * 1024 - SETTINGS_BLOCK;
+/// the block is split into slots of `SETTINGS_LEN`: the current settings first, then the presets
+const SETTINGS_BLOCK: usize = 8 * 1024;
+const _: () = assert!(SETTINGS_LEN * (PRESETS + 1) <= SETTINGS_BLOCK);
+
 pub type Neopixel = ws2812::Ws2812<SpinTimer, OldOutputPin<Pin<PA27, Output<PushPull>>>>;
+pub type Accelerometer = Adxl343<bsp::I2C>;
 
 pub struct Board {
     // pub peripherals: Peripherals,
@@ -23,6 +40,13 @@ pub struct Board {
     pub delay: Delay,
     pub neopixel: Neopixel,
     pub keypad: Keypad,
+    pub nvm: Nvm,
+    /// `None` if the accelerometer didn't answer on startup
+    pub accel: Option<Accelerometer>,
+
+    /// cpu cycles since startup, kept in 64 bits since the cycle counter wraps every ~35s
+    cycles: u64,
+    last_cycle_count: u32,
 }
 
 impl Board {
@@ -40,6 +64,10 @@ impl Board {
 
         let delay = Delay::new(core.SYST, &mut clocks);
 
+        // used to keep time
+        core.DCB.enable_trace();
+        core.DWT.enable_cycle_counter();
+
         let mut pins = bsp::Pins::new(peripherals.PORT).split();
 
         // neo pixel
@@ -50,6 +78,17 @@ impl Board {
 
         let keypad = bsp::Keypad::new(pins.keypad, &mut pins.port);
 
+        // the onboard ADXL343, on the i2c bus of SERCOM2
+        let accel = pins
+            .accel
+            .open(
+                &mut clocks,
+                peripherals.SERCOM2,
+                &mut peripherals.MCLK,
+                &mut pins.port,
+            )
+            .ok();
+
         crate::usb::setup_usb(
             &mut peripherals.MCLK,
             peripherals.USB,
@@ -58,11 +97,73 @@ impl Board {
             &mut clocks,
         );
 
+        let nvm = Nvm::new(peripherals.NVMCTRL);
+
         Self {
             clocks,
             delay,
             neopixel,
             keypad,
+            nvm,
+            accel,
+
+            cycles: 0,
+            last_cycle_count: DWT::cycle_count(),
+        }
+    }
+
+    /// Returns the microseconds since startup. Needs to be called at least every ~35s
+    pub fn micros(&mut self) -> u64 {
+        let count = DWT::cycle_count();
+        self.cycles += count.wrapping_sub(self.last_cycle_count) as u64;
+        self.last_cycle_count = count;
+        self.cycles / CYCLES_PER_MICRO
+    }
+
+    /// Converts a cycle count read in the last ~35s into microseconds since startup
+    pub fn micros_at(&mut self, cycle_count: u32) -> u64 {
+        let elapsed = DWT::cycle_count().wrapping_sub(cycle_count) as u64 / CYCLES_PER_MICRO;
+        self.micros().saturating_sub(elapsed)
+    }
+
+    /// Reads the accelerometer, if it's there
+    pub fn read_accel(&mut self) -> Option<Sample> {
+        let raw = self.accel.as_mut()?.accel_raw().ok()?;
+        Some(Sample {
+            x: raw.x,
+            y: raw.y,
+            z: raw.z,
+        })
+    }
+
+    /// Reads the settings stored in `slot`
+    pub fn read_settings(&self, slot: usize, buf: &mut [u8; SETTINGS_LEN]) {
+        let address = SETTINGS_ADDRESS + slot * SETTINGS_LEN;
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = unsafe { core::ptr::read_volatile((address + i) as *const u8) };
+        }
+    }
+
+    /// Stores settings in `slot`. The whole block is erased, so the other slots are written back too
+    pub fn write_settings(&mut self, slot: usize, buf: &[u8; SETTINGS_LEN]) {
+        let mut words = [0u32; SETTINGS_BLOCK / 4];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = unsafe { core::ptr::read_volatile((SETTINGS_ADDRESS + i * 4) as *const u32) };
+        }
+        let slot_words = &mut words[slot * SETTINGS_LEN / 4..(slot + 1) * SETTINGS_LEN / 4];
+        for (word, bytes) in slot_words.iter_mut().zip(buf.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        unsafe {
+            let _ = self
+                .nvm
+                .erase_flash(SETTINGS_ADDRESS as *mut u32, 1, EraseGranularity::Block);
+            let _ = self.nvm.write_flash(
+                SETTINGS_ADDRESS as *mut u32,
+                words.as_ptr(),
+                words.len() as u32,
+            );
         }
     }
 }
@@ -0,0 +1,227 @@
+use usbd_midi::midi_types::MidiMessage;
+
+use crate::{state::State, usb::send_message};
+
+/// MIDI clock resolution, in ticks per quarter note
+pub const PPQN: u32 = 24;
+
+pub const MIN_BPM: u16 = 30;
+pub const MAX_BPM: u16 = 280;
+
+/// taps further apart than this start a new tap tempo, in microseconds
+const TAP_TIMEOUT: u64 = 2_000_000;
+const MAX_TAPS: usize = 4;
+
+/// we lose sync after not receiving clock for this long, in microseconds
+const SYNC_TIMEOUT: u64 = 500_000;
+/// external clocks closer than this arrived in the same usb transfer, so the time between them
+/// says nothing about the tempo. half a tick at the fastest tempo, in microseconds
+const MIN_EXTERNAL_INTERVAL: u64 = 60_000_000 / (MAX_BPM as u64 * PPQN as u64) / 2;
+
+pub struct Clock {
+    pub bpm: u16,
+    /// time of the last tick, in microseconds
+    last_tick: u64,
+
+    /// whether the transport is playing
+    pub running: bool,
+    /// whether to send MIDI clock and transport messages
+    pub output: bool,
+    /// ticks since the transport was started
+    pub position: u32,
+    /// ticks since the last beat, counted even when the transport is stopped
+    pub beat: u32,
+    /// beats since power on, wrapping around, for steps longer than `beat` can count
+    pub beats: u32,
+
+    /// whether to follow incoming MIDI clock and transport
+    pub sync: bool,
+    /// time the last external clock arrived at, in microseconds
+    last_external: u64,
+    /// smoothed time between external clocks, in microseconds
+    external_interval: u64,
+    /// external clocks received since the last update
+    external_ticks: u32,
+    /// external clocks that arrived too close to `last_external` to be timed
+    untimed_ticks: u32,
+
+    /// intervals between the last taps, in microseconds
+    taps: [u64; MAX_TAPS],
+    tap_count: usize,
+    last_tap: u64,
+}
+
+impl Clock {
+    pub const fn new() -> Self {
+        Self {
+            bpm: 120,
+            last_tick: 0,
+
+            running: false,
+            output: true,
+            position: 0,
+            beat: 0,
+            beats: 0,
+
+            sync: true,
+            last_external: 0,
+            external_interval: 0,
+            external_ticks: 0,
+            untimed_ticks: 0,
+
+            taps: [0; MAX_TAPS],
+            tap_count: 0,
+            last_tap: 0,
+        }
+    }
+
+    /// Length of a tick, in microseconds
+    pub fn tick_length(&self) -> u64 {
+        60_000_000 / (self.bpm as u64 * PPQN as u64)
+    }
+
+    /// Returns whether we are following an external clock
+    pub fn synced(&self, now: u64) -> bool {
+        self.sync && self.external_interval != 0 && now - self.last_external < SYNC_TIMEOUT
+    }
+
+    /// Tracks the tempo of a clock received at `time`
+    pub fn external_tick(&mut self, time: u64) {
+        if !self.sync {
+            return;
+        }
+
+        self.external_ticks += 1;
+        let interval = time.saturating_sub(self.last_external);
+        // the next timed interval gets spread over the clocks that shared a timestamp
+        if interval < MIN_EXTERNAL_INTERVAL {
+            self.untimed_ticks += 1;
+            return;
+        }
+        self.last_external = time;
+        let clocks = core::mem::take(&mut self.untimed_ticks) as u64 + 1;
+
+        if interval > SYNC_TIMEOUT {
+            self.external_interval = 0;
+            return;
+        }
+        let interval = interval / clocks;
+
+        // usb delivers clocks in bursts, so smooth the jitter out before following the tempo
+        self.external_interval = if self.external_interval == 0 {
+            interval
+        } else {
+            (self.external_interval * 7 + interval) / 8
+        };
+        let bpm = 60_000_000 / (self.external_interval * PPQN as u64).max(1);
+        self.bpm = (bpm as u16).clamp(MIN_BPM, MAX_BPM);
+    }
+
+    /// Returns how many ticks happened since the last call
+    pub fn update(&mut self, now: u64) -> u32 {
+        let len = self.tick_length();
+        let ticks = (now - self.last_tick) / len;
+
+        // don't try to catch up after a long stall, like when writing to flash
+        if ticks > PPQN as u64 {
+            self.last_tick = now;
+            return 1;
+        }
+
+        self.last_tick += ticks * len;
+        ticks as u32
+    }
+
+    /// Sets the tempo from the time between taps
+    pub fn tap(&mut self, now: u64) {
+        let interval = now - self.last_tap;
+        self.last_tap = now;
+
+        if interval > TAP_TIMEOUT {
+            self.tap_count = 0;
+            return;
+        }
+
+        self.taps.rotate_right(1);
+        self.taps[0] = interval;
+        self.tap_count = (self.tap_count + 1).min(MAX_TAPS);
+
+        let average = self.taps[..self.tap_count].iter().sum::<u64>() / self.tap_count as u64;
+        self.bpm = ((60_000_000 / average) as u16).clamp(MIN_BPM, MAX_BPM);
+        // line the beat up with the last tap
+        self.last_tick = now;
+        self.beat = 0;
+    }
+
+    /// Returns whether we're in the first half of a beat, for blinking leds in time
+    pub fn on_beat(&self) -> bool {
+        self.beat < PPQN / 2
+    }
+}
+
+impl State {
+    /// Advances the clock, returning how many ticks happened since the last call
+    pub fn update_clock(&mut self) -> u32 {
+        let now = self.board.micros();
+        let internal = self.clock.update(now);
+
+        let synced = self.clock.synced(now);
+        let ticks = if synced {
+            core::mem::take(&mut self.clock.external_ticks)
+        } else {
+            self.clock.external_ticks = 0;
+            internal
+        };
+
+        // when following another clock, it's up to the host to send clock to everyone else
+        if self.clock.output && !synced {
+            for _ in 0..ticks {
+                send_message(MidiMessage::TimingClock);
+            }
+        }
+        if self.clock.running {
+            self.clock.position += ticks;
+        }
+        let beat = self.clock.beat + ticks;
+        self.clock.beats = self.clock.beats.wrapping_add(beat / PPQN);
+        self.clock.beat = beat % PPQN;
+
+        ticks
+    }
+
+    /// Starts the transport from the beginning
+    pub fn start(&mut self) {
+        self.clock.running = true;
+        self.clock.position = 0;
+        self.clock.beat = 0;
+        if self.clock.output {
+            send_message(MidiMessage::Start);
+        }
+    }
+
+    /// Starts the transport from where it was stopped
+    pub fn resume(&mut self) {
+        self.clock.running = true;
+        if self.clock.output {
+            send_message(MidiMessage::Continue);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.clock.running = false;
+        if self.clock.output {
+            send_message(MidiMessage::Stop);
+        }
+    }
+
+    /// Returns whether we are following an external clock
+    pub fn synced(&mut self) -> bool {
+        let now = self.board.micros();
+        self.clock.synced(now)
+    }
+
+    pub fn tap_tempo(&mut self) {
+        let now = self.board.micros();
+        self.clock.tap(now);
+    }
+}
@@ -0,0 +1,219 @@
+use core::fmt::{self, Write};
+
+use crate::{
+    settings::Param,
+    state::State,
+    sysex::SysexBuffer,
+    usb::{flush_serial, read_serial, write_serial},
+};
+
+/// longest command line
+const LINE_LEN: usize = 64;
+/// bytes of log kept for the `log` command, dropping the oldest lines
+const LOG_LEN: usize = 1024;
+
+const HELP: &str = "commands:
+  state            show what the matriu is doing
+  params           list every setting as id and value
+  get ID           show a setting
+  set ID VALUE     change a setting, kept after `save`
+  save             store the settings in flash
+  log              show recent log messages
+  panic            stop every note
+ids are the ones in src/settings.rs, in decimal or hex like 0x41
+";
+
+/// A text console on the USB serial port
+pub struct Console {
+    line: [u8; LINE_LEN],
+    len: usize,
+    log: [u8; LOG_LEN],
+    log_len: usize,
+    /// sysex sent on the serial port instead of over midi, like by the cli
+    sysex: SysexBuffer,
+}
+
+impl Console {
+    pub const fn new() -> Self {
+        Self {
+            line: [0; LINE_LEN],
+            len: 0,
+            log: [0; LOG_LEN],
+            log_len: 0,
+            sysex: SysexBuffer::new(),
+        }
+    }
+}
+
+/// Queues output for the serial port, turning line endings into the `\r\n` terminals expect
+struct Serial;
+
+impl Write for Serial {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                write_serial(b"\r\n");
+            }
+            write_serial(line.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+/// Keeps log messages in the console's buffer
+struct Log<'a>(&'a mut Console);
+
+impl Write for Log<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let console = &mut *self.0;
+        for &byte in s.as_bytes().iter().rev().take(LOG_LEN).rev() {
+            if console.log_len == LOG_LEN {
+                // drop the oldest line
+                let end = console
+                    .log
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(1, |i| i + 1);
+                console.log.copy_within(end.., 0);
+                console.log_len -= end;
+            }
+            console.log[console.log_len] = byte;
+            console.log_len += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a number in decimal, or in hex starting with `0x`
+fn parse_number(s: &str) -> Option<u16> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+impl State {
+    /// Logs a message, keeping it for the `log` command.
+    /// nothing is sent from here, so logging never waits on the host
+    pub fn log(&mut self, args: fmt::Arguments) {
+        let _ = writeln!(Log(&mut self.console), "{args}");
+    }
+
+    /// Handles what was typed on the console since the last call, and sends what's left of the output
+    pub fn update_console(&mut self) {
+        while let Some(byte) = read_serial() {
+            // typed text never goes above 0x7f, so sysex can share the port with it
+            if byte == 0xf0 || self.console.sysex.receiving() {
+                if let Some(len) = self.console.sysex.push_byte(byte) {
+                    let message = self.console.sysex.buf;
+                    self.handle_sysex(&message[..len], write_serial);
+                }
+                continue;
+            }
+            match byte {
+                b'\r' | b'\n' => {
+                    let _ = writeln!(Serial);
+                    let (line, len) = (self.console.line, self.console.len);
+                    self.console.len = 0;
+                    if let Ok(line) = core::str::from_utf8(&line[..len]) {
+                        self.run_command(line);
+                    }
+                    let _ = write!(Serial, "> ");
+                }
+                // backspace and delete
+                0x08 | 0x7f if self.console.len > 0 => {
+                    self.console.len -= 1;
+                    write_serial(b"\x08 \x08");
+                }
+                0x20..=0x7e if self.console.len < LINE_LEN => {
+                    self.console.line[self.console.len] = byte;
+                    self.console.len += 1;
+                    // echo, since terminals don't show what's typed on their own
+                    write_serial(&[byte]);
+                }
+                _ => {}
+            }
+        }
+        flush_serial();
+    }
+
+    fn run_command(&mut self, line: &str) {
+        let mut words = line.split_whitespace();
+        let (command, a, b) = (words.next(), words.next(), words.next());
+        let param = a.and_then(parse_number).and_then(Param::from_id);
+
+        let value = b.and_then(parse_number).and_then(|n| u8::try_from(n).ok());
+
+        let _ = match (command, param, value) {
+            (None, _, _) => Ok(()),
+            (Some("help"), _, _) => write!(Serial, "{HELP}"),
+            (Some("state"), _, _) => self.write_state(),
+            (Some("params"), _, _) => {
+                for param in Param::all() {
+                    let _ = writeln!(Serial, "{:#05x} {}", param.id(), self.get(param));
+                }
+                Ok(())
+            }
+            (Some("get"), Some(param), _) => writeln!(Serial, "{}", self.get(param)),
+            (Some("set"), Some(param), Some(value)) => {
+                self.set(param, value);
+                self.check_zones();
+                writeln!(Serial, "{}", self.get(param))
+            }
+            (Some("get" | "set"), None, _) => writeln!(Serial, "unknown param"),
+            (Some("save"), _, _) => {
+                self.save_settings();
+                writeln!(Serial, "saved")
+            }
+            (Some("log"), _, _) => {
+                let log = &self.console.log[..self.console.log_len];
+                write!(Serial, "{}", core::str::from_utf8(log).unwrap_or(""))
+            }
+            (Some("panic"), _, _) => {
+                self.panic(true);
+                Ok(())
+            }
+            (Some(command), _, _) => writeln!(Serial, "unknown command {command}, try help"),
+        };
+    }
+
+    fn write_state(&mut self) -> fmt::Result {
+        writeln!(Serial, "mode {:?}, home {:?}", self.mode, self.home)?;
+        writeln!(
+            Serial,
+            "root {:?} {:?}, channel {}, velocity {}",
+            self.root,
+            self.scale,
+            self.channel + 1,
+            self.velocity
+        )?;
+        writeln!(
+            Serial,
+            "bpm {}, {}{}",
+            self.clock.bpm,
+            if self.clock.running {
+                "running"
+            } else {
+                "stopped"
+            },
+            if self.clock.sync {
+                ", following the host"
+            } else {
+                ""
+            }
+        )?;
+        for z in 0..self.zone_count {
+            let zone = self.zones[z];
+            writeln!(
+                Serial,
+                "zone {} from column {}: {:?}, octave {}, channel {}",
+                z + 1,
+                zone.start,
+                zone.keyboard,
+                zone.octave,
+                self.zone_channel(z) + 1
+            )?;
+        }
+        writeln!(Serial, "{} notes playing", self.notes_playing())
+    }
+}
@@ -0,0 +1,184 @@
+use crate::{clock::PPQN, state::State};
+
+pub const LANES: usize = 8;
+/// steps in a pattern, one bit each
+pub const DRUM_STEPS: u32 = 8;
+pub const PATTERNS: usize = 4;
+/// steps are sixteenth notes
+const STEP_TICKS: u32 = PPQN / 4;
+
+#[derive(Copy, Clone)]
+pub struct Lane {
+    pub note: u8,
+    pub channel: u8,
+    pub mute: bool,
+    pub solo: bool,
+}
+
+pub struct Drums {
+    pub lanes: [Lane; LANES],
+    /// for every pattern, the steps each lane plays on as a bitmask
+    pub patterns: [[u8; LANES]; PATTERNS],
+
+    /// lane shown on the grid
+    pub lane: usize,
+    /// pattern being played and edited
+    pub pattern: usize,
+    /// patterns played one after the other, as a range
+    pub chain: (usize, usize),
+    /// pattern to switch to once the current one ends
+    pub queued: Option<usize>,
+
+    /// step the playhead is on, `None` while stopped
+    pub position: Option<u8>,
+    /// lanes with a note sounding from the pattern, as a bitmask
+    pub playing: u8,
+    /// lanes being played from their pads, as a bitmask. the pattern doesn't turn them off
+    held: u8,
+}
+
+impl Lane {
+    /// general midi drums play on channel 10
+    const fn new(note: u8) -> Self {
+        Self {
+            note,
+            channel: 9,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+impl Drums {
+    pub const fn new() -> Self {
+        Self {
+            // kick, snare, closed hat, open hat, clap, low tom, high tom, crash
+            lanes: [
+                Lane::new(36),
+                Lane::new(38),
+                Lane::new(42),
+                Lane::new(46),
+                Lane::new(39),
+                Lane::new(45),
+                Lane::new(50),
+                Lane::new(49),
+            ],
+            patterns: [[0; LANES]; PATTERNS],
+
+            lane: 0,
+            pattern: 0,
+            chain: (0, 0),
+            queued: None,
+
+            position: None,
+            playing: 0,
+            held: 0,
+        }
+    }
+
+    pub fn step(&self, lane: usize, step: u32) -> bool {
+        self.patterns[self.pattern][lane] & (1 << step) != 0
+    }
+
+    pub fn toggle_step(&mut self, step: u32) {
+        self.patterns[self.pattern][self.lane] ^= 1 << step;
+    }
+
+    /// Returns whether `lane` can be heard, taking mute and solo into account
+    pub fn audible(&self, lane: usize) -> bool {
+        let solo = self.lanes.iter().any(|l| l.solo);
+        !self.lanes[lane].mute && (!solo || self.lanes[lane].solo)
+    }
+
+    /// Plays `pattern` next, or chains every pattern from `pattern` to `until`
+    pub fn select_pattern(&mut self, pattern: usize, until: Option<usize>) {
+        let until = until.unwrap_or(pattern);
+        self.chain = (pattern.min(until), pattern.max(until));
+        if self.position.is_some() {
+            self.queued = Some(self.chain.0);
+        } else {
+            self.pattern = self.chain.0;
+        }
+    }
+
+    /// Returns the pattern that comes after the current one
+    fn next_pattern(&mut self) -> usize {
+        if let Some(queued) = self.queued.take() {
+            queued
+        } else if self.pattern >= self.chain.1 || self.pattern < self.chain.0 {
+            self.chain.0
+        } else {
+            self.pattern + 1
+        }
+    }
+}
+
+impl State {
+    /// Plays the drum patterns, following the transport. They keep playing while the keyboards are played
+    pub fn update_drums(&mut self) {
+        if !self.clock.running {
+            self.drums_notes_off();
+            self.drums.position = None;
+            return;
+        }
+
+        let position = self.clock.position / STEP_TICKS % DRUM_STEPS;
+        // drum hits are short, so turn them off halfway through the step
+        if self.clock.position % STEP_TICKS >= STEP_TICKS / 2 {
+            self.drums_notes_off();
+        }
+
+        if self.drums.position == Some(position as u8) {
+            return;
+        }
+        if position == 0 && self.drums.position.is_some() {
+            self.drums.pattern = self.drums.next_pattern();
+        }
+        self.drums.position = Some(position as u8);
+        self.drums_notes_off();
+
+        for lane in 0..LANES {
+            if self.drums.step(lane, position) && self.drums.audible(lane) {
+                self.play_lane(lane, true);
+            }
+        }
+    }
+
+    /// Plays or stops the sound of `lane` from the pattern
+    fn play_lane(&mut self, lane: usize, on: bool) {
+        let Lane { note, channel, .. } = self.drums.lanes[lane];
+        self.send_midi(channel, note, on);
+        if on {
+            self.drums.playing |= 1 << lane;
+        } else {
+            self.drums.playing &= !(1 << lane);
+        }
+    }
+
+    /// Plays or stops the sound of `lane` while its pad is held
+    pub fn preview_lane(&mut self, lane: usize, on: bool) {
+        let Lane { note, channel, .. } = self.drums.lanes[lane];
+        if on {
+            self.drums.held |= 1 << lane;
+        } else if self.drums.held & (1 << lane) != 0 {
+            self.drums.held &= !(1 << lane);
+        } else {
+            return;
+        }
+        self.send_midi(channel, note, on);
+    }
+
+    fn drums_notes_off(&mut self) {
+        for lane in 0..LANES {
+            if self.drums.playing & (1 << lane) == 0 {
+                continue;
+            }
+            // a held lane keeps sounding until its pad is let go of
+            if self.drums.held & (1 << lane) != 0 {
+                self.drums.playing &= !(1 << lane);
+            } else {
+                self.play_lane(lane, false);
+            }
+        }
+    }
+}
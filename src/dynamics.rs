@@ -0,0 +1,101 @@
+use crate::state::State;
+
+/// pad that plays accents while held, when the source is `VelocitySource::Accent`
+pub const ACCENT_PAD: (u8, u8) = (7, 0);
+/// max distance random velocities can be from the fixed one
+pub const MAX_SPREAD: u8 = 64;
+
+/// Where the velocity of pads comes from
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum VelocitySource {
+    /// the velocity set in config mode
+    Fixed,
+    /// bottom row soft, top row loud
+    Row,
+    /// the fixed velocity, give or take `spread`
+    Random,
+    /// the fixed velocity, or full velocity while holding `ACCENT_PAD`
+    Accent,
+}
+
+impl VelocitySource {
+    pub const fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Fixed,
+            1 => Self::Row,
+            2 => Self::Random,
+            3 => Self::Accent,
+            _ => panic!("number is not in 0..4"),
+        }
+    }
+}
+
+/// How velocities are shaped before being sent
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Curve {
+    Linear,
+    /// raises soft velocities, so everything plays louder
+    Soft,
+    /// lowers soft velocities, leaving more room for loud ones
+    Hard,
+}
+
+impl Curve {
+    pub const fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Linear,
+            1 => Self::Soft,
+            2 => Self::Hard,
+            _ => panic!("number is not in 0..3"),
+        }
+    }
+
+    pub fn apply(self, velocity: u8) -> u8 {
+        let v = velocity as u32;
+        let shaped = match self {
+            Curve::Linear => v,
+            Curve::Soft => 127 - (127 - v) * (127 - v) / 127,
+            Curve::Hard => v * v / 127,
+        };
+        shaped.clamp(1, 127) as u8
+    }
+}
+
+pub struct Dynamics {
+    pub source: VelocitySource,
+    pub curve: Curve,
+    /// how far random velocities can be from the fixed one
+    pub spread: u8,
+}
+
+impl Dynamics {
+    pub const fn new() -> Self {
+        Self {
+            source: VelocitySource::Fixed,
+            curve: Curve::Linear,
+            spread: 20,
+        }
+    }
+}
+
+impl State {
+    /// Returns the velocity the pad at `(col, row)` plays with, after the response curve
+    pub fn velocity_for(&mut self, col: u8, row: u8) -> u8 {
+        let velocity = match self.dynamics.source {
+            // a measured strike is more expressive than any of the others
+            _ if self.strike.enabled => self.strike_velocity(col, row),
+            VelocitySource::Fixed => self.velocity,
+            VelocitySource::Row => 127 - row * 29,
+            VelocitySource::Random => {
+                let spread = self.dynamics.spread as u32;
+                let offset = self.random() % (spread * 2 + 1);
+                (self.velocity as u32 + offset)
+                    .saturating_sub(spread)
+                    .min(127) as u8
+            }
+            VelocitySource::Accent if self.key_pressed(ACCENT_PAD) => 127,
+            VelocitySource::Accent => self.velocity,
+        };
+        self.dynamics.curve.apply(velocity)
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{clock::PPQN, state::State};
+
+pub const RHYTHMS: usize = 4;
+pub const MAX_EUCLID_STEPS: u8 = 16;
+/// two octaves of scale degrees
+pub const MAX_EUCLID_DEGREE: u8 = 7 * 2 - 1;
+/// steps are sixteenth notes
+const STEP_TICKS: u32 = PPQN / 4;
+
+#[derive(Copy, Clone)]
+pub struct Rhythm {
+    /// from 1 to `MAX_EUCLID_STEPS`
+    pub steps: u8,
+    /// how many of the steps play, spread as evenly as possible
+    pub pulses: u8,
+    pub rotation: u8,
+    /// scale degree played, counted from the root of the zone's octave
+    pub degree: u8,
+    pub mute: bool,
+}
+
+impl Rhythm {
+    const fn new(degree: u8) -> Self {
+        Self {
+            steps: MAX_EUCLID_STEPS,
+            pulses: 0,
+            rotation: 0,
+            degree,
+            mute: false,
+        }
+    }
+
+    /// Returns whether `step` is a pulse
+    pub fn pulse(&self, step: u32) -> bool {
+        let steps = self.steps as u32;
+        let step = (step + steps - self.rotation as u32 % steps) % steps;
+        // spreads the pulses the same way bresenham spreads a line's pixels
+        (step * self.pulses as u32) % steps < self.pulses as u32
+    }
+}
+
+pub struct Euclid {
+    /// one rhythm per row, from top to bottom
+    pub rhythms: [Rhythm; RHYTHMS],
+    /// step the playhead is on, `None` while stopped
+    pub position: Option<u32>,
+    /// notes sounding, one per rhythm
+    playing: [Option<(u8, u8)>; RHYTHMS],
+}
+
+impl Euclid {
+    pub const fn new() -> Self {
+        Self {
+            rhythms: [
+                Rhythm::new(7),
+                Rhythm::new(4),
+                Rhythm::new(2),
+                Rhythm::new(0),
+            ],
+            position: None,
+            playing: [None; RHYTHMS],
+        }
+    }
+
+    /// Returns the step rhythm `r` is on
+    pub fn step(&self, r: usize) -> Option<u32> {
+        self.position.map(|p| p % self.rhythms[r].steps as u32)
+    }
+}
+
+impl State {
+    /// Plays the euclidean rhythms, following the transport. They keep playing while the keyboards are played
+    pub fn update_euclid(&mut self) {
+        if !self.clock.running {
+            self.euclid_notes_off();
+            self.euclid.position = None;
+            return;
+        }
+
+        let position = self.clock.position / STEP_TICKS;
+        if self.clock.position % STEP_TICKS >= STEP_TICKS / 2 {
+            self.euclid_notes_off();
+        }
+        if self.euclid.position == Some(position) {
+            return;
+        }
+        self.euclid.position = Some(position);
+        self.euclid_notes_off();
+
+        let channel = self.zone_channel(self.zone);
+        for r in 0..RHYTHMS {
+            let rhythm = self.euclid.rhythms[r];
+            if rhythm.mute || !rhythm.pulse(position % rhythm.steps as u32) {
+                continue;
+            }
+
+            let note = self.degree_note(rhythm.degree);
+            self.send_midi(channel, note, true);
+            self.euclid.playing[r] = Some((channel, note));
+        }
+    }
+
+    fn euclid_notes_off(&mut self) {
+        for r in 0..RHYTHMS {
+            if let Some((channel, note)) = self.euclid.playing[r].take() {
+                self.send_midi(channel, note, false);
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{state::State, usb::send_cc};
+
+/// one fader per playable column
+pub const FADERS: usize = 7;
+/// values set by the pads of a fader, from the bottom row up
+pub const FADER_LEVELS: [u8; 4] = [0, 42, 85, 127];
+
+#[derive(Copy, Clone)]
+pub struct Fader {
+    pub cc: u8,
+    /// `None` follows the global channel
+    pub channel: Option<u8>,
+    /// last value sent
+    pub value: u8,
+}
+
+impl Fader {
+    const fn new(cc: u8) -> Self {
+        Self {
+            cc,
+            channel: None,
+            value: 0,
+        }
+    }
+}
+
+/// Cutoff, resonance, attack, release, volume, pan and reverb
+pub const DEFAULT_FADERS: [Fader; FADERS] = [
+    Fader::new(74),
+    Fader::new(71),
+    Fader::new(73),
+    Fader::new(72),
+    Fader::new(7),
+    Fader::new(10),
+    Fader::new(91),
+];
+
+impl State {
+    /// Moves fader `f` to `value`, sending its CC
+    pub fn set_fader(&mut self, f: usize, value: u8) {
+        let fader = &mut self.faders[f];
+        fader.value = value.min(127);
+        send_cc(fader.channel.unwrap_or(self.channel), fader.cc, fader.value);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::state::{Keyboard, State};
+
+/// Intervals the harmonizer can add, in scale steps: 3rd, 5th, 6th and octave
+pub const HARMONY_STEPS: [u8; 4] = [2, 4, 5, 7];
+/// max number of notes the harmonizer adds to a single note
+pub const MAX_HARMONY: usize = HARMONY_STEPS.len() * 2;
+
+/// Doubles the notes of the scale keyboard with diatonic intervals
+pub struct Harmony {
+    /// intervals added above the played note, as a bitmask of `HARMONY_STEPS`
+    pub above: u8,
+    /// intervals added below the played note, as a bitmask of `HARMONY_STEPS`
+    pub below: u8,
+}
+
+impl Harmony {
+    pub const fn new() -> Self {
+        Self { above: 0, below: 0 }
+    }
+}
+
+impl State {
+    /// Returns the notes the harmonizer adds to `note`, played by a pad on column `col`.
+    /// intervals follow the current scale and root, so a 3rd can be major or minor
+    pub fn harmony_notes(&self, col: u8, note: u8) -> ([u8; MAX_HARMONY], usize) {
+        let mut notes = [0; MAX_HARMONY];
+        let mut len = 0;
+        if self.zones[self.zone_at(col)].keyboard != Keyboard::Scale {
+            return (notes, len);
+        }
+        let Some(degree) = self.scale.degree(note + 12 - self.root as u8) else {
+            return (notes, len);
+        };
+
+        for (i, &steps) in HARMONY_STEPS.iter().enumerate() {
+            if self.harmony.above & (1 << i) != 0 {
+                let up = note + self.scale.interval(degree, steps);
+                if up <= 126 {
+                    notes[len] = up;
+                    len += 1;
+                }
+            }
+            if self.harmony.below & (1 << i) != 0 {
+                // going up `steps` from the degree below lands back on the played one
+                let down = (degree + 7 - steps) % 7;
+                if let Some(down) = note.checked_sub(self.scale.interval(down, steps)) {
+                    notes[len] = down;
+                    len += 1;
+                }
+            }
+        }
+        (notes, len)
+    }
+}
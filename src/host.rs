@@ -0,0 +1,104 @@
+use smart_leds::hsv::{hsv2rgb, Hsv, RGB8};
+use trellis_m4 as bsp;
+use usbd_midi::midi_types;
+
+use crate::{
+    keys::KeyIndex,
+    state::{Mode, State},
+    usb::send_midi,
+};
+
+/// Lets the host set the color of every pad, and reports pad presses back as notes
+pub struct Host {
+    /// channel LED messages are received on, and pad presses are sent on
+    pub channel: u8,
+    /// palette index of each pad, by key index
+    pub colors: [u8; bsp::NEOPIXEL_COUNT],
+    /// notes the host is playing on each channel other than `channel`, as bitmasks
+    pub incoming: [u128; 16],
+    /// home mode to go back to when host mode is turned off
+    pub previous_home: Mode,
+}
+
+impl Host {
+    pub const fn new() -> Self {
+        Self {
+            // out of the way of the channels notes are usually played on
+            channel: 15,
+            colors: [0; bsp::NEOPIXEL_COUNT],
+            incoming: [0; 16],
+            previous_home: Mode::Normal,
+        }
+    }
+}
+
+/// Returns the note or CC a pad is addressed with, numbered like a launchpad in programmer mode:
+/// the tens are the row counting from the bottom, and the units the column counting from 1
+pub fn host_note(col: u8, row: u8) -> u8 {
+    10 * (4 - row) + col + 1
+}
+
+/// Returns the key index of the pad addressed by `note`, the inverse of `host_note`
+pub fn host_pad(note: u8) -> Option<usize> {
+    let (tens, units) = (note / 10, note % 10);
+    if !(1..=4).contains(&tens) || !(1..=8).contains(&units) {
+        return None;
+    }
+    Some((units - 1, 4 - tens).into_index())
+}
+
+/// Returns the color of a palette index, laid out like the launchpad palette:
+/// black, three whites, then groups of four shades of each hue going around the color wheel
+pub fn palette(index: u8) -> RGB8 {
+    let index = index.min(127);
+    if index < 4 {
+        let val = [0, 64, 128, 255][index as usize];
+        return RGB8 {
+            r: val,
+            g: val,
+            b: val,
+        };
+    }
+
+    let (group, shade) = ((index - 4) / 4, (index - 4) % 4);
+    // the first shade of each group is washed out, then full, dim and dimmer
+    let (sat, val) = [(160, 255), (255, 255), (255, 128), (255, 48)][shade as usize];
+    hsv2rgb(Hsv {
+        hue: group * 8,
+        sat,
+        val,
+    })
+}
+
+impl State {
+    /// Sets the color of the pad addressed by `note` to a palette index. Notes that don't address a pad are ignored
+    pub fn set_host_color(&mut self, note: u8, color: u8) {
+        if let Some(i) = host_pad(note) {
+            self.host.colors[i] = color;
+        }
+    }
+
+    /// Keeps track of a note the host is playing, so the pads that play it can be lit
+    pub fn set_incoming(&mut self, channel: u8, note: u8, on: bool) {
+        let incoming = &mut self.host.incoming[channel as usize & 0x0f];
+        if on {
+            *incoming |= 1 << (note & 0x7f);
+        } else {
+            *incoming &= !(1 << (note & 0x7f));
+        }
+    }
+
+    /// Returns whether the host is playing the note the pad at `(col, row)` plays
+    pub fn incoming(&self, col: u8, row: u8) -> bool {
+        let (channel, note, intervals) = self.pad_notes(col, row);
+        let note = note + intervals.first().copied().unwrap_or(0);
+        note < 128 && self.host.incoming[channel as usize] & (1 << note) != 0
+    }
+
+    /// Reports a pad being pressed or released to the host
+    pub fn send_host_pad(&mut self, col: u8, row: u8, on: bool) {
+        let velocity = if on { self.velocity_for(col, row) } else { 0 };
+        let note = midi_types::Note::new(host_note(col, row));
+        send_midi(self.host.channel, note, velocity, on);
+    }
+}
@@ -16,6 +16,51 @@ impl KeyState {
     }
 }
 
+/// how long presses of the first column are held back for, in case they're part of the panic chord, in microseconds
+const CHORD_WINDOW: u64 = 40_000;
+
+/// Holds back presses of the first column for a moment, in case more of them are pressed together
+/// for the panic chord, and passes them on to the modes if they aren't
+pub struct Column {
+    /// keys held down, by row, whether or not they've been passed on yet
+    pub held: u8,
+    /// keys passed on as pressed, by row
+    pub passed: u8,
+    /// when the keys waiting to be passed on started being pressed
+    pressed_at: u64,
+    /// whether the keys held down were pressed together, so they're a panic and not their own actions
+    pub chord: bool,
+}
+
+impl Column {
+    pub const fn new() -> Self {
+        Self {
+            held: 0,
+            passed: 0,
+            pressed_at: 0,
+            chord: false,
+        }
+    }
+
+    pub fn update(&mut self, held: u8, now: u64) {
+        self.held = held;
+        self.passed &= held;
+
+        let waiting = self.held & !self.passed;
+        if self.held == 0 {
+            self.chord = false;
+        } else if waiting.count_ones() >= 2 {
+            self.chord = true;
+        }
+
+        if waiting == 0 || self.chord {
+            self.pressed_at = now;
+        } else if now - self.pressed_at >= CHORD_WINDOW {
+            self.passed |= waiting;
+        }
+    }
+}
+
 pub trait KeyIndex {
     fn into_index(self) -> usize;
 }
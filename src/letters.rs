@@ -77,3 +77,69 @@ pub fn letter(note: Note) -> [u8; 16] {
         ],
     }
 }
+
+/// returns an array describing how to display a digit in a 3x4 rectangle
+pub fn digit(n: u8) -> [u8; 12] {
+    match n {
+        0 => [
+            1, 1, 1, //
+            1, 0, 1, //
+            1, 0, 1, //
+            1, 1, 1, //
+        ],
+        1 => [
+            0, 1, 0, //
+            1, 1, 0, //
+            0, 1, 0, //
+            1, 1, 1, //
+        ],
+        2 => [
+            1, 1, 0, //
+            0, 0, 1, //
+            0, 1, 0, //
+            1, 1, 1, //
+        ],
+        3 => [
+            1, 1, 1, //
+            0, 1, 1, //
+            0, 0, 1, //
+            1, 1, 1, //
+        ],
+        4 => [
+            1, 0, 1, //
+            1, 0, 1, //
+            1, 1, 1, //
+            0, 0, 1, //
+        ],
+        5 => [
+            1, 1, 1, //
+            1, 1, 0, //
+            0, 0, 1, //
+            1, 1, 0, //
+        ],
+        6 => [
+            1, 0, 0, //
+            1, 1, 1, //
+            1, 0, 1, //
+            1, 1, 1, //
+        ],
+        7 => [
+            1, 1, 1, //
+            0, 0, 1, //
+            0, 1, 0, //
+            0, 1, 0, //
+        ],
+        8 => [
+            1, 1, 1, //
+            1, 1, 1, //
+            1, 0, 1, //
+            1, 1, 1, //
+        ],
+        _ => [
+            1, 1, 1, //
+            1, 0, 1, //
+            1, 1, 1, //
+            0, 0, 1, //
+        ],
+    }
+}
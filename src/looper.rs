@@ -0,0 +1,211 @@
+use crate::{clock::PPQN, state::State};
+
+/// max number of note events a loop can hold
+pub const MAX_EVENTS: usize = 256;
+/// max number of notes the looper can have sounding at once
+const MAX_SOUNDING: usize = 16;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum LoopState {
+    Empty,
+    /// starts recording on the next played note
+    Armed,
+    Recording,
+    Playing,
+    Overdubbing,
+    Stopped,
+}
+
+#[derive(Copy, Clone)]
+struct Event {
+    /// clock ticks since the start of the loop
+    tick: u32,
+    channel: u8,
+    note: u8,
+    /// velocity it was played at, so playback keeps the dynamics
+    velocity: u8,
+    on: bool,
+    /// overdub this event was recorded in, starting at 0
+    layer: u8,
+}
+
+pub struct Looper {
+    pub state: LoopState,
+
+    events: [Event; MAX_EVENTS],
+    len: usize,
+    /// number of layers recorded, counting the first recording
+    layers: u8,
+
+    /// length of the loop, in clock ticks
+    length: u32,
+    /// clock ticks since the start of the loop
+    tick: u32,
+
+    /// notes turned on by the looper, so they can be turned off when the loop wraps
+    sounding: [(u8, u8); MAX_SOUNDING],
+    sounding_len: usize,
+
+    /// whether the looper pad was held long enough to trigger its action
+    pub long_press: bool,
+}
+
+impl Looper {
+    pub const fn new() -> Self {
+        Self {
+            state: LoopState::Empty,
+
+            events: [Event {
+                tick: 0,
+                channel: 0,
+                note: 0,
+                velocity: 0,
+                on: false,
+                layer: 0,
+            }; MAX_EVENTS],
+            len: 0,
+            layers: 0,
+
+            length: 0,
+            tick: 0,
+
+            sounding: [(0, 0); MAX_SOUNDING],
+            sounding_len: 0,
+
+            long_press: false,
+        }
+    }
+
+    /// Records a note played live, if the looper is recording
+    pub fn record(&mut self, channel: u8, note: u8, velocity: u8, on: bool) {
+        if self.state == LoopState::Armed && on {
+            self.state = LoopState::Recording;
+            self.tick = 0;
+            self.layers = 1;
+        }
+        if !matches!(self.state, LoopState::Recording | LoopState::Overdubbing) {
+            return;
+        }
+        if self.len == MAX_EVENTS {
+            return;
+        }
+
+        self.events[self.len] = Event {
+            tick: self.tick,
+            channel,
+            note,
+            velocity,
+            on,
+            layer: self.layers - 1,
+        };
+        self.len += 1;
+    }
+
+    /// Moves to the next state, as done by tapping the looper pad
+    pub fn tap(&mut self) {
+        self.state = match self.state {
+            LoopState::Empty => LoopState::Armed,
+            LoopState::Armed => LoopState::Empty,
+            LoopState::Recording => {
+                // round the loop up to a whole beat so it stays in time
+                self.length = self.tick.div_ceil(PPQN).max(1) * PPQN;
+                // events played right as the loop ends belong to its start
+                for event in &mut self.events[..self.len] {
+                    event.tick %= self.length;
+                }
+                LoopState::Playing
+            }
+            LoopState::Playing => {
+                // past the last layer, overdubs go into it and undo together
+                self.layers = self.layers.saturating_add(1);
+                LoopState::Overdubbing
+            }
+            LoopState::Overdubbing | LoopState::Stopped => LoopState::Playing,
+        };
+    }
+
+    /// Removes the last overdub, keeping the first recording
+    pub fn undo(&mut self) {
+        if self.layers <= 1 {
+            return;
+        }
+        self.layers -= 1;
+
+        let mut len = 0;
+        for i in 0..self.len {
+            if self.events[i].layer < self.layers {
+                self.events[len] = self.events[i];
+                len += 1;
+            }
+        }
+        self.len = len;
+
+        if self.state == LoopState::Overdubbing {
+            self.state = LoopState::Playing;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl State {
+    /// Plays the loop for `ticks` clock ticks
+    pub fn update_looper(&mut self, ticks: u32) {
+        match self.looper.state {
+            LoopState::Recording => {
+                self.looper.tick += ticks;
+                return;
+            }
+            LoopState::Playing | LoopState::Overdubbing => {}
+            _ => {
+                self.looper_notes_off();
+                return;
+            }
+        }
+
+        // go tick by tick so events play in order
+        for _ in 0..ticks {
+            self.looper.tick += 1;
+            if self.looper.tick >= self.looper.length {
+                self.looper.tick = 0;
+                self.looper_notes_off();
+            }
+
+            for i in 0..self.looper.len {
+                let event = self.looper.events[i];
+                if event.tick != self.looper.tick {
+                    continue;
+                }
+
+                let sounding = &self.looper.sounding[..self.looper.sounding_len];
+                let pos = sounding
+                    .iter()
+                    .position(|&n| n == (event.channel, event.note));
+                match (event.on, pos) {
+                    (true, None) if self.looper.sounding_len < MAX_SOUNDING => {
+                        self.looper.sounding[self.looper.sounding_len] =
+                            (event.channel, event.note);
+                        self.looper.sounding_len += 1;
+                    }
+                    (false, Some(pos)) => {
+                        self.looper.sounding[pos] =
+                            self.looper.sounding[self.looper.sounding_len - 1];
+                        self.looper.sounding_len -= 1;
+                    }
+                    _ => continue,
+                }
+                self.replay_note(event.channel, event.note, event.velocity, event.on);
+            }
+        }
+    }
+
+    fn looper_notes_off(&mut self) {
+        for i in 0..self.looper.sounding_len {
+            let (channel, note) = self.looper.sounding[i];
+            self.route_note(channel, note, false);
+        }
+        self.looper.sounding_len = 0;
+    }
+}
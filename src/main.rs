@@ -17,16 +17,45 @@ use smart_leds::{
     SmartLedsWrite,
 };
 
+use arp::{ArpMode, ARP_RATES};
+use clock::{MAX_BPM, MIN_BPM};
+use drums::{DRUM_STEPS, LANES, PATTERNS};
+use dynamics::{Curve, VelocitySource, ACCENT_PAD, MAX_SPREAD};
+use euclid::{MAX_EUCLID_DEGREE, MAX_EUCLID_STEPS, RHYTHMS};
+use faders::{FADERS, FADER_LEVELS};
+use harmony::{Harmony, HARMONY_STEPS};
+use host::palette;
+use looper::LoopState;
+use mono::Priority;
+use motion::UNITY_GAIN;
 use music_theory::{Note, Scale};
+use sequencer::{SeqParam, MAX_DEGREE, PAGE_STEPS};
 use state::{Keyboard, Mode, State, MAX_OCTAVE};
+use tilt::{TiltTarget, MAX_TILT_DEADZONE, MAX_TILT_RANGE, MIN_TILT_RANGE};
 
-use crate::music_theory::Chord;
-
+mod arp;
 mod board;
+mod clock;
+mod console;
+mod drums;
+mod dynamics;
+mod euclid;
+mod faders;
+mod harmony;
+mod host;
 mod keys;
 mod letters;
+mod looper;
+mod midi_in;
+mod mono;
 mod music_theory;
+mod repeat;
+mod sequencer;
+mod settings;
 mod state;
+mod strike;
+mod sysex;
+mod tilt;
 mod usb;
 
 #[entry]
@@ -36,12 +65,26 @@ fn main() -> ! {
     loop {
         state.board.delay.delay_ms(5u8);
         state.update_keys();
+        state.update_accel();
+        state.read_midi();
+        state.update_console();
+        let ticks = state.update_clock();
 
+        update_panic(&mut state);
         run(&mut state);
+        state.update_looper(ticks);
+        state.update_arp(ticks);
+        state.update_repeat();
+        state.update_sequencer();
+        state.update_drums();
+        state.update_euclid();
         update_colors(&mut state);
     }
 }
 
+/// how long control pads need to be held for in sequencer mode, in microseconds
+const LONG_PRESS: u64 = 400_000;
+
 fn run(state: &mut State) {
     match state.mode {
         Mode::Normal => {
@@ -53,96 +96,38 @@ fn run(state: &mut State) {
             }
 
             state.update_sustain();
+            update_looper_pad(state);
 
-            match state.keyboard {
-                Keyboard::Scale => {
-                    for col in 1..8 {
-                        for row in 0..4 {
-                            let note = (state.octave + row) * 12
-                                + state.scale.get(col - 1)
-                                + state.root as u8;
-                            if state.key_just_pressed((col, row)) {
-                                state.send_midi(note, true);
-                            } else if state.key_just_released((col, row)) {
-                                state.send_midi(note, false);
-                            }
-                        }
-                    }
-                }
-                Keyboard::Chords => {
-                    for col in 1..8 {
-                        macro_rules! chords {
-                            ($row:expr, $chord:path) => {
-                                if state.key_just_pressed((col, $row)) {
-                                    let root = (state.octave) * 12
-                                        + state.scale.get(col - 1)
-                                        + state.root as u8;
-
-                                    for note in $chord.notes() {
-                                        state.send_midi(note + root, true);
-                                    }
-                                } else if state.key_just_released((col, $row)) {
-                                    let root = (state.octave) * 12
-                                        + state.scale.get(col - 1)
-                                        + state.root as u8;
-
-                                    for note in $chord.notes() {
-                                        state.send_midi(note + root, false);
-                                    }
-                                }
-                            };
-                        }
-                        if state.keyboard == Keyboard::Chords {
-                            chords!(0, Chord::Major);
-                            chords!(1, Chord::Minor);
-                            chords!(2, Chord::Diminished);
-                            chords!(3, Chord::Power);
-                        }
+            // while note repeat is on, holding the fourth key turns the first row into rate selection
+            let select_rate = state.repeat.enabled && state.key_pressed((0, 3));
+            if select_rate {
+                for i in 0..ARP_RATES.len() as u8 {
+                    if state.key_just_pressed((i + 1, 0)) {
+                        state.repeat.rate = i;
+                        state.sustain_key_used = true;
                     }
                 }
-                Keyboard::Sampler => {
-                    for col in 0..7 {
-                        for row in 0..4 {
-                            let note =
-                                state.octave * 12 + state.root as u8 + (3 - row) + (6 - col) * 4;
-                            if state.key_just_pressed((col + 1, row)) {
-                                state.send_midi(note, true);
-                            } else if state.key_just_released((col + 1, row)) {
-                                state.send_midi(note, false);
-                            }
-                        }
+            }
+
+            for col in 1..8 {
+                for row in 0..4 {
+                    if select_rate && row == 0 {
+                        continue;
                     }
-                }
-                Keyboard::Bass => {
-                    macro_rules! bass {
-                        ($row:expr, $offset:expr) => {
-                            for col in 0..7 {
-                                let note = state.octave * 12 + state.root as u8 + $offset + col;
-                                if state.key_just_pressed((col + 1, $row)) {
-                                    state.send_midi(note, true);
-                                } else if state.key_just_released((col + 1, $row)) {
-                                    state.send_midi(note, false);
-                                }
-                            }
-                        };
+                    // the accent pad doesn't play, it makes the other pads louder
+                    if state.dynamics.source == VelocitySource::Accent && (col, row) == ACCENT_PAD {
+                        continue;
                     }
+                    // with latch on, pressing a pad turns its notes on or off and releasing does nothing
+                    let on = if state.key_just_pressed((col, row)) {
+                        !state.latch || state.toggle_latched((col, row))
+                    } else if state.key_just_released((col, row)) && !state.latch {
+                        false
+                    } else {
+                        continue;
+                    };
 
-                    bass!(3, 0);
-                    bass!(2, 5);
-                    bass!(1, 10);
-                    bass!(0, 15);
-                }
-                Keyboard::Waffletone => {
-                    for col in 0..7 {
-                        for row in 0..4 {
-                            let note = state.octave * 12 + state.root as u8 - row + col * 3;
-                            if state.key_just_pressed((col + 1, row)) {
-                                state.send_midi(note, true);
-                            } else if state.key_just_released((col + 1, row)) {
-                                state.send_midi(note, false);
-                            }
-                        }
-                    }
+                    state.play_pad(col, row, on);
                 }
             }
         }
@@ -153,19 +138,20 @@ fn run(state: &mut State) {
 
             if hold {
                 if state.key_just_pressed((0, 0)) || state.key_just_pressed((0, 1)) {
-                    state.set_mode(Mode::Normal);
+                    state.set_mode(state.home);
                 }
             } else if !state.key_pressed((0, 0)) {
-                state.set_mode(Mode::Normal);
+                state.set_mode(state.home);
             }
 
+            let channel = state.zone_channel(state.zone);
             macro_rules! select_note {
                 ($note:expr, $pos:expr) => {
                     if state.key_just_pressed($pos) {
                         state.root = $note;
-                        state.send_midi(6 * 12 + $note as u8, true);
+                        state.send_midi(channel, 6 * 12 + $note as u8, true);
                     } else if state.key_just_released($pos) {
-                        state.send_midi(6 * 12 + $note as u8, false);
+                        state.send_midi(channel, 6 * 12 + $note as u8, false);
                     }
                 };
             }
@@ -185,7 +171,25 @@ fn run(state: &mut State) {
         }
         Mode::Config => {
             if !state.key_pressed((0, 1)) {
-                state.set_mode(Mode::Normal);
+                state.set_mode(state.home);
+            }
+            if state.key_just_pressed((0, 2)) {
+                state.set_mode(Mode::Zones);
+            }
+            if state.key_just_pressed((1, 1)) {
+                state.set_mode(Mode::Arp);
+            }
+            if state.key_just_pressed((2, 1)) {
+                state.set_mode(Mode::Tempo);
+            }
+            if state.key_just_pressed((3, 1)) {
+                state.repeat.enabled = !state.repeat.enabled;
+            }
+            if state.key_just_pressed((4, 2)) {
+                state.set_mode(Mode::Harmony);
+            }
+            if state.key_just_pressed((5, 2)) {
+                state.latch = !state.latch;
             }
 
             for i in 0..7 {
@@ -195,21 +199,33 @@ fn run(state: &mut State) {
             }
 
             if state.key_just_pressed((0, 3)) {
-                state.note_off_all();
+                state.panic(true);
+            }
+            if state.key_just_pressed((0, 0)) {
+                state.set_sustain(false);
+                state.sustain_cc = !state.sustain_cc;
             }
 
+            if state.key_just_pressed((4, 1)) {
+                state.channel = state.channel.saturating_sub(1);
+            }
+            if state.key_just_pressed((5, 1)) {
+                state.channel = state.channel.saturating_add(1).min(15);
+            }
             if state.key_just_pressed((6, 1)) {
                 state.velocity = state.velocity.saturating_sub(5).max(5);
             }
             if state.key_just_pressed((7, 1)) {
                 state.velocity = state.velocity.saturating_add(5).min(126);
             }
+            let zone = state.zone;
             if state.key_just_pressed((6, 2)) {
-                state.octave = state.octave.saturating_sub(1);
+                state.zones[zone].octave = state.zones[zone].octave.saturating_sub(1);
             }
             if state.key_just_pressed((7, 2)) {
                 // TODO not sure what the maximum number should be here
-                state.octave = state.octave.saturating_add(1).min(MAX_OCTAVE);
+                state.zones[zone].octave =
+                    state.zones[zone].octave.saturating_add(1).min(MAX_OCTAVE);
             }
             if state.key_just_pressed((6, 3)) {
                 state.brightness = state.brightness.saturating_sub(5).max(5);
@@ -218,22 +234,610 @@ fn run(state: &mut State) {
                 state.brightness = state.brightness.saturating_add(5);
             }
 
-            macro_rules! keyboard {
-                ($i:expr, $k:expr) => {
-                    if state.key_just_pressed(($i, 3)) {
-                        state.keyboard = $k;
+            select_keyboard(state, 3);
+            if (1..6).any(|i| state.key_just_pressed((i, 3))) {
+                state.home = Mode::Normal;
+            }
+            if state.key_just_pressed((1, 2)) {
+                state.home = Mode::Sequencer;
+            }
+            if state.key_just_pressed((2, 2)) {
+                state.home = Mode::Drums;
+            }
+            if state.key_just_pressed((3, 2)) {
+                state.home = Mode::Euclid;
+            }
+        }
+        Mode::Zones => {
+            if state.key_just_pressed((0, 1)) || state.key_just_pressed((0, 2)) {
+                state.set_mode(state.home);
+            }
+
+            for col in 2..8 {
+                if state.key_just_pressed((col, 0)) {
+                    state.toggle_split(col);
+                }
+            }
+            for col in 1..8 {
+                if state.key_just_pressed((col, 1)) {
+                    state.zone = state.zone_at(col);
+                }
+            }
+
+            select_keyboard(state, 2);
+
+            let zone = state.zone;
+            if state.key_just_pressed((6, 2)) {
+                state.zones[zone].octave = state.zones[zone].octave.saturating_sub(1);
+            }
+            if state.key_just_pressed((7, 2)) {
+                state.zones[zone].octave =
+                    state.zones[zone].octave.saturating_add(1).min(MAX_OCTAVE);
+            }
+            if state.key_just_pressed((6, 3)) {
+                // going below the first channel makes the zone follow the global one
+                state.zones[zone].channel =
+                    state.zones[zone].channel.and_then(|c| c.checked_sub(1));
+            }
+            if state.key_just_pressed((7, 3)) {
+                state.zones[zone].channel =
+                    Some(state.zones[zone].channel.map_or(0, |c| (c + 1).min(15)));
+            }
+
+            if state.key_just_pressed((1, 3)) {
+                if state.home == Mode::Host {
+                    state.home = state.host.previous_home;
+                } else {
+                    state.host.previous_home = state.home;
+                    state.home = Mode::Host;
+                }
+            }
+            if state.key_just_pressed((3, 3)) {
+                state.host.channel = state.host.channel.saturating_sub(1);
+            }
+            if state.key_just_pressed((4, 3)) {
+                state.host.channel = (state.host.channel + 1).min(15);
+            }
+        }
+        Mode::Harmony => {
+            if state.key_just_pressed((0, 1)) {
+                state.set_mode(state.home);
+            }
+
+            for i in 0..HARMONY_STEPS.len() as u8 {
+                if state.key_just_pressed((i + 1, 0)) {
+                    state.harmony.above ^= 1 << i;
+                }
+                if state.key_just_pressed((i + 1, 1)) {
+                    state.harmony.below ^= 1 << i;
+                }
+            }
+            if state.key_just_pressed((7, 3)) {
+                state.harmony = Harmony::new();
+            }
+
+            if state.key_just_pressed((1, 2)) {
+                state.home = Mode::Faders;
+            }
+            if state.key_just_pressed((2, 2)) {
+                state.set_mode(Mode::Tilt);
+            }
+            if state.key_just_pressed((3, 2)) {
+                state.set_mode(Mode::Velocity);
+            }
+
+            if state.key_just_pressed((1, 3)) {
+                state.mono.enabled = !state.mono.enabled;
+            }
+            for i in 0..3 {
+                if state.key_just_pressed((i + 2, 3)) {
+                    state.mono.priority = Priority::from(i);
+                }
+            }
+            if state.key_just_pressed((5, 3)) {
+                state.mono.legato = !state.mono.legato;
+            }
+        }
+        Mode::Velocity => {
+            if state.key_just_pressed((0, 1)) {
+                state.set_mode(Mode::Harmony);
+            }
+
+            for i in 0..4 {
+                if state.key_just_pressed((i + 1, 0)) {
+                    state.dynamics.source = VelocitySource::from(i);
+                }
+            }
+            for i in 0..3 {
+                if state.key_just_pressed((i + 1, 1)) {
+                    state.dynamics.curve = Curve::from(i);
+                }
+            }
+            if state.key_just_pressed((1, 2)) {
+                state.dynamics.spread = state.dynamics.spread.saturating_sub(4);
+            }
+            if state.key_just_pressed((2, 2)) {
+                state.dynamics.spread = (state.dynamics.spread + 4).min(MAX_SPREAD);
+            }
+            if state.key_just_pressed((6, 2)) {
+                state.velocity = state.velocity.saturating_sub(5).max(5);
+            }
+            if state.key_just_pressed((7, 2)) {
+                state.velocity = state.velocity.saturating_add(5).min(126);
+            }
+        }
+        Mode::Tilt => {
+            if state.key_just_pressed((0, 1)) {
+                state.set_mode(Mode::Harmony);
+            }
+
+            if state.key_just_pressed((1, 0)) {
+                if state.tilt.enabled {
+                    state.release_tilt();
+                }
+                state.tilt.enabled = !state.tilt.enabled;
+            }
+            if state.key_just_pressed((2, 0)) {
+                state.tilt.motion.calibrate();
+            }
+            for i in 0..4 {
+                if state.key_just_pressed((i + 4, 0)) {
+                    state.tilt.motion.smoothing = i * 2;
+                }
+            }
+
+            // the second row sets up tilting left and right, and the third forward and back
+            for axis in 0..2 {
+                let row = axis as u8 + 1;
+                for i in 0..3 {
+                    if state.key_just_pressed((i + 1, row)) {
+                        state.release_tilt();
+                        state.tilt.axes[axis].target = TiltTarget::from(i);
+                    }
+                }
+                if state.key_just_pressed((6, row)) {
+                    state.tilt.axes[axis].cc = state.tilt.axes[axis].cc.saturating_sub(1);
+                }
+                if state.key_just_pressed((7, row)) {
+                    state.tilt.axes[axis].cc = (state.tilt.axes[axis].cc + 1).min(127);
+                }
+            }
+
+            if state.key_just_pressed((1, 3)) {
+                state.tilt.motion.deadzone = (state.tilt.motion.deadzone - 4).max(0);
+            }
+            if state.key_just_pressed((2, 3)) {
+                state.tilt.motion.deadzone =
+                    (state.tilt.motion.deadzone + 4).min(MAX_TILT_DEADZONE);
+            }
+            if state.key_just_pressed((4, 3)) {
+                state.tilt.motion.range = (state.tilt.motion.range - 16).max(MIN_TILT_RANGE);
+            }
+            if state.key_just_pressed((5, 3)) {
+                state.tilt.motion.range = (state.tilt.motion.range + 16).min(MAX_TILT_RANGE);
+            }
+
+            if state.key_just_pressed((6, 3)) {
+                state.strike.enabled = !state.strike.enabled;
+            }
+            if state.key_just_pressed((7, 3)) {
+                state.set_mode(Mode::Strike);
+            }
+        }
+        Mode::Strike => {
+            if state.key_just_pressed((0, 1)) {
+                state.set_mode(Mode::Tilt);
+            }
+            if state.key_just_pressed((0, 3)) {
+                state.strike.gains = [[UNITY_GAIN; 7]; 4];
+            }
+
+            for col in 1..8 {
+                for row in 0..4 {
+                    if state.key_just_pressed((col, row)) {
+                        state.calibrate_strike(col, row);
+                    }
+                }
+            }
+        }
+        Mode::Arp => {
+            if state.key_just_pressed((0, 1)) {
+                state.set_mode(state.home);
+            }
+
+            if state.key_just_pressed((1, 0)) {
+                state.arp.enabled = !state.arp.enabled;
+                state.arp.clear();
+            }
+            if state.key_just_pressed((2, 0)) {
+                state.arp.latch = !state.arp.latch;
+                state.arp.clear();
+            }
+            for i in 0..4 {
+                if state.key_just_pressed((i + 4, 0)) {
+                    state.arp.gate = i + 1;
+                }
+            }
+            for i in 0..6 {
+                if state.key_just_pressed((i + 1, 1)) {
+                    state.arp.mode = ArpMode::from(i);
+                }
+                if state.key_just_pressed((i + 1, 2)) {
+                    state.arp.rate = i;
+                }
+            }
+            for i in 0..4 {
+                if state.key_just_pressed((i + 1, 3)) {
+                    state.arp.octaves = i + 1;
+                }
+            }
+
+            if state.key_just_pressed((6, 3)) {
+                state.clock.bpm = state.clock.bpm.saturating_sub(5).max(MIN_BPM);
+            }
+            if state.key_just_pressed((7, 3)) {
+                state.clock.bpm = state.clock.bpm.saturating_add(5).min(MAX_BPM);
+            }
+        }
+        Mode::Tempo => {
+            if state.key_just_pressed((0, 1)) {
+                state.set_mode(state.home);
+            }
+
+            if state.key_just_pressed((0, 0)) {
+                state.clock.output = !state.clock.output;
+            }
+            if state.key_just_pressed((0, 2)) && !state.clock.running {
+                state.resume();
+            }
+            // a tap on the fourth key toggles sync, holding it starts and stops the transport
+            if update_transport_key(state) {
+                state.clock.sync = !state.clock.sync;
+            }
+
+            if state.key_just_pressed((4, 0)) {
+                state.tap_tempo();
+            }
+            if state.key_just_pressed((4, 1)) {
+                state.clock.bpm = state.clock.bpm.saturating_add(1).min(MAX_BPM);
+            }
+            if state.key_just_pressed((4, 2)) {
+                state.clock.bpm = state.clock.bpm.saturating_sub(1).max(MIN_BPM);
+            }
+        }
+        Mode::Sequencer => {
+            // the control column edits step 0 like any other column,
+            // and does its usual thing when held, undoing the edit
+            for row in 0..4 {
+                let r = row as usize;
+                if state.key_just_pressed((0, row)) {
+                    let i = state.sequencer.step_at(0);
+                    state.sequencer.undo[r] = Some((i, state.sequencer.steps[i]));
+                    state.sequencer.edit(0, row);
+                }
+                if !state.key_pressed((0, row)) {
+                    state.sequencer.undo[r] = None;
+                }
+
+                if state.key_held_for((0, row)) < LONG_PRESS {
+                    continue;
+                }
+                let Some((i, step)) = state.sequencer.undo[r].take() else {
+                    continue;
+                };
+                state.sequencer.steps[i] = step;
+                match row {
+                    0 => state.set_mode(Mode::SelectRoot { hold: false }),
+                    1 => state.set_mode(Mode::Config),
+                    2 => state.set_mode(Mode::SequencerMenu),
+                    _ if state.clock.running => state.stop(),
+                    _ => state.start(),
+                }
+                return;
+            }
+
+            for col in 1..8 {
+                for row in 0..4 {
+                    if state.key_just_pressed((col, row)) {
+                        state.sequencer.edit(col, row);
+                    }
+                }
+            }
+        }
+        Mode::SequencerMenu => {
+            if !state.key_pressed((0, 2)) {
+                state.set_mode(Mode::Sequencer);
+            }
+
+            let pages = state.sequencer.length / PAGE_STEPS as u8;
+            for i in 0..4 {
+                if state.key_just_pressed((i + 1, 0)) {
+                    state.sequencer.param = SeqParam::from(i);
+                }
+                for (row, page) in [(1, i), (2, i + 4)] {
+                    if state.key_just_pressed((i + 1, row)) && page < pages {
+                        state.sequencer.page = page;
+                        state.sequencer.follow = false;
+                    }
+                }
+            }
+            for (i, length) in [16, 32, 64].into_iter().enumerate() {
+                if state.key_just_pressed((i as u8 + 5, 0)) {
+                    state.sequencer.length = length;
+                    state.sequencer.page = state.sequencer.page.min(length / PAGE_STEPS as u8 - 1);
+                }
+            }
+
+            if state.key_just_pressed((6, 1)) {
+                state.sequencer.view = state.sequencer.view.saturating_sub(1);
+            }
+            if state.key_just_pressed((7, 1)) {
+                state.sequencer.view = (state.sequencer.view + 1).min(MAX_DEGREE - 4);
+            }
+            if state.key_just_pressed((6, 2)) {
+                state.sequencer.follow = !state.sequencer.follow;
+            }
+            if state.key_just_pressed((7, 3)) {
+                state.sequencer.clear();
+            }
+        }
+        Mode::Drums => {
+            if state.key_pressed((0, 1)) {
+                state.set_mode(Mode::Config);
+                return;
+            }
+            update_transport_key(state);
+
+            // holding a lane key on the first column turns the steps into lanes
+            let select = state.key_pressed((0, 0));
+            let mute = state.key_pressed((0, 2));
+            let solo = state.key_pressed((0, 3));
+            for lane in 0..LANES {
+                let pad = step_pad(lane as u32);
+                if state.key_just_released(pad) {
+                    state.preview_lane(lane, false);
+                }
+                if !state.key_just_pressed(pad) {
+                    continue;
+                }
+
+                if mute {
+                    state.drums.lanes[lane].mute = !state.drums.lanes[lane].mute;
+                } else if solo {
+                    state.drums.lanes[lane].solo = !state.drums.lanes[lane].solo;
+                } else if select {
+                    state.drums.lane = lane;
+                    state.preview_lane(lane, true);
+                } else {
+                    state.drums.toggle_step(lane as u32);
+                }
+            }
+
+            // while selecting lanes, the third row is the selected lane's settings
+            if select {
+                let pressed = (1..5).find(|&col| state.key_just_pressed((col, 2)));
+                let lane = &mut state.drums.lanes[state.drums.lane];
+                match pressed {
+                    Some(1) => lane.note = lane.note.saturating_sub(1),
+                    Some(2) => lane.note = (lane.note + 1).min(126),
+                    Some(3) => lane.channel = lane.channel.saturating_sub(1),
+                    Some(4) => lane.channel = (lane.channel + 1).min(15),
+                    _ => {}
+                }
+            }
+
+            // holding a pattern and pressing another chains every pattern between them
+            for pattern in 0..PATTERNS {
+                if state.key_just_pressed((7, pattern as u8)) {
+                    let held =
+                        (0..PATTERNS).find(|&p| p != pattern && state.key_pressed((7, p as u8)));
+                    match held {
+                        Some(held) => state.drums.select_pattern(held, Some(pattern)),
+                        None => state.drums.select_pattern(pattern, None),
                     }
+                }
+            }
+        }
+        Mode::Euclid => {
+            if state.key_pressed((0, 1)) {
+                state.set_mode(Mode::Config);
+                return;
+            }
+            update_transport_key(state);
+
+            let edit = state.key_pressed((0, 2));
+            let pick_note = state.key_pressed((0, 3));
+            for r in 0..RHYTHMS {
+                let Some(col) = (1..8).find(|&col| state.key_just_pressed((col, r as u8))) else {
+                    continue;
                 };
+                let rhythm = &mut state.euclid.rhythms[r];
+
+                if edit {
+                    match col {
+                        1 => rhythm.steps = rhythm.steps.saturating_sub(1).max(1),
+                        2 => rhythm.steps = (rhythm.steps + 1).min(MAX_EUCLID_STEPS),
+                        3 => rhythm.pulses = rhythm.pulses.saturating_sub(1),
+                        4 => rhythm.pulses += 1,
+                        5 => rhythm.rotation = rhythm.rotation.saturating_sub(1),
+                        6 => rhythm.rotation += 1,
+                        _ => rhythm.mute = !rhythm.mute,
+                    }
+                    rhythm.pulses = rhythm.pulses.min(rhythm.steps);
+                    rhythm.rotation = rhythm.rotation.min(rhythm.steps - 1);
+                } else if pick_note {
+                    // picking the same degree again moves it up an octave, and back down
+                    let degree = col - 1;
+                    rhythm.degree = if rhythm.degree == degree {
+                        degree + 7
+                    } else {
+                        degree
+                    }
+                    .min(MAX_EUCLID_DEGREE);
+                } else {
+                    rhythm.mute = !rhythm.mute;
+                }
+            }
+        }
+        Mode::Host => {
+            if state.key_pressed((0, 1)) {
+                state.set_mode(Mode::Config);
+                return;
+            }
+
+            for col in 0..8 {
+                for row in 0..4 {
+                    if (col, row) == (0, 1) {
+                        continue;
+                    }
+                    if state.key_just_pressed((col, row)) {
+                        state.send_host_pad(col, row, true);
+                    }
+                    if state.key_just_released((col, row)) {
+                        state.send_host_pad(col, row, false);
+                    }
+                }
+            }
+        }
+        Mode::Faders => {
+            if state.key_pressed((0, 1)) {
+                state.set_mode(Mode::Config);
+                return;
+            }
+            if state.key_just_pressed((0, 0)) {
+                // send every value again, to bring the synth in line with the faders
+                for f in 0..FADERS {
+                    state.set_fader(f, state.faders[f].value);
+                }
+            }
+
+            let bind = state.key_pressed((0, 2));
+            let fine = state.key_pressed((0, 3));
+            for f in 0..FADERS {
+                let col = f as u8 + 1;
+                let Some(row) = (0..4).find(|&row| state.key_just_pressed((col, row))) else {
+                    continue;
+                };
+
+                let fader = &mut state.faders[f];
+                if bind {
+                    match row {
+                        0 => fader.cc = fader.cc.saturating_sub(1),
+                        1 => fader.cc = (fader.cc + 1).min(127),
+                        // going below the first channel makes the fader follow the global one
+                        2 => fader.channel = fader.channel.and_then(|c| c.checked_sub(1)),
+                        _ => fader.channel = Some(fader.channel.map_or(0, |c| (c + 1).min(15))),
+                    }
+                } else if fine {
+                    let value = match row {
+                        0 => fader.value.saturating_add(8),
+                        1 => fader.value.saturating_add(1),
+                        2 => fader.value.saturating_sub(1),
+                        _ => fader.value.saturating_sub(8),
+                    };
+                    state.set_fader(f, value);
+                } else {
+                    state.set_fader(f, FADER_LEVELS[3 - row as usize]);
+                }
             }
-            keyboard!(1, Keyboard::Scale);
-            keyboard!(2, Keyboard::Chords);
-            keyboard!(3, Keyboard::Sampler);
-            keyboard!(4, Keyboard::Bass);
-            keyboard!(5, Keyboard::Waffletone);
         }
     }
 }
 
+/// Pressing the four control pads at once panics from any mode,
+/// and keeping them held also sends note off for every note.
+/// the modes don't see the pads while they're pressed together, so they don't do anything else
+fn update_panic(state: &mut State) {
+    if !state.column.chord || state.column.held != 0b1111 {
+        state.panic_at = None;
+        return;
+    }
+
+    let now = state.board.micros();
+    match state.panic_at {
+        None => {
+            state.panic(false);
+            state.panic_at = Some(now);
+            state.panic_long_press = false;
+        }
+        Some(at) if !state.panic_long_press && now - at >= LONG_PRESS => {
+            state.panic(true);
+            state.panic_long_press = true;
+        }
+        _ => {}
+    }
+}
+
+/// Holding the fourth key starts and stops the transport, the same way in every mode that plays along with it.
+/// pressing pads while holding it uses it as a modifier instead, like solo in drum mode.
+/// Returns whether it was tapped, let go before a long press without being used that way
+fn update_transport_key(state: &mut State) -> bool {
+    if state.key_just_pressed((0, 3)) {
+        state.transport_long_press = false;
+    }
+    if !state.key_pressed((0, 3)) {
+        return state.key_just_released((0, 3)) && !state.transport_long_press;
+    }
+
+    if (0..32u8).any(|i| i != 24 && state.key_just_pressed(i)) {
+        state.transport_long_press = true;
+    }
+    if !state.transport_long_press && state.key_held_for((0, 3)) >= LONG_PRESS {
+        state.transport_long_press = true;
+        if state.clock.running {
+            state.stop();
+        } else {
+            state.start();
+        }
+    }
+    false
+}
+
+/// Taps on the third key step through arm, record, play and overdub.
+/// holding it stops the loop, or clears it if it's already stopped,
+/// and pressing the fourth key while holding it undoes the last overdub
+fn update_looper_pad(state: &mut State) {
+    if state.key_just_pressed((0, 2)) {
+        state.looper.long_press = false;
+    }
+    if state.key_pressed((0, 2)) && state.key_just_pressed((0, 3)) {
+        state.looper.undo();
+        state.looper.long_press = true;
+    }
+
+    if state.key_just_released((0, 2)) && !state.looper.long_press {
+        state.looper.tap();
+    } else if !state.looper.long_press && state.key_held_for((0, 2)) >= LONG_PRESS {
+        state.looper.long_press = true;
+        match state.looper.state {
+            LoopState::Playing | LoopState::Overdubbing => state.looper.state = LoopState::Stopped,
+            _ => state.looper.clear(),
+        }
+    }
+}
+
+/// Returns the pad used for drum `step`, in two rows of four next to the first column.
+/// the same pads select lanes while holding a lane key
+fn step_pad(step: u32) -> (u8, u8) {
+    (1 + step as u8 % 4, step as u8 / 4)
+}
+
+/// Sets the keyboard of the selected zone using columns 1-5 of `row`
+fn select_keyboard(state: &mut State, row: u8) {
+    macro_rules! keyboard {
+        ($i:expr, $k:expr) => {
+            if state.key_just_pressed(($i, row)) {
+                state.zones[state.zone].keyboard = $k;
+            }
+        };
+    }
+    keyboard!(1, Keyboard::Scale);
+    keyboard!(2, Keyboard::Chords);
+    keyboard!(3, Keyboard::Sampler);
+    keyboard!(4, Keyboard::Bass);
+    keyboard!(5, Keyboard::Waffletone);
+}
+
 fn update_colors(state: &mut State) {
     let mut colors = [colors::BLACK; bsp::NEOPIXEL_COUNT];
 
@@ -255,58 +859,75 @@ fn update_colors(state: &mut State) {
 
             colors[24] = if state.sustain {
                 colors::BLUE
+            } else if state.repeat.enabled {
+                colors::ORANGE
             } else {
                 colors::CYAN
             };
+            let synced = state.synced();
+            colors[16] = match state.looper.state {
+                LoopState::Empty if synced => sync_color(state),
+                LoopState::Empty => colors::BLACK,
+                LoopState::Armed if state.clock.on_beat() => colors::YELLOW,
+                LoopState::Armed => colors::BLACK,
+                LoopState::Recording => colors::RED,
+                LoopState::Playing => colors::GREEN,
+                LoopState::Overdubbing => colors::PURPLE,
+                LoopState::Stopped => colors::CYAN,
+            };
 
-            match state.keyboard {
-                Keyboard::Scale | Keyboard::Chords | Keyboard::Sampler => {
-                    for col in 1..8 {
-                        for row in 0..4 {
-                            colors[(col, row).into_index()] = if state.key_pressed((col, row)) {
+            let notes = state.scale.notes();
+            for col in 1..8 {
+                let keyboard = state.zones[state.zone_at(col)].keyboard;
+                for row in 0..4 {
+                    let (_, note, _) = state.pad_notes(col, row);
+                    let v = (note + 12 - state.root as u8) % 12;
+
+                    colors[(col, row).into_index()] = match keyboard {
+                        Keyboard::Scale | Keyboard::Chords | Keyboard::Sampler => {
+                            if state.key_pressed((col, row)) || state.latched((col, row)) {
                                 hue(row * 64)
+                            } else if state.incoming(col, row) {
+                                colors::MAGENTA
                             } else {
                                 colors::BLACK
-                            };
-                        }
-                    }
-                }
-                Keyboard::Bass => {
-                    let notes = state.scale.notes();
-                    macro_rules! bass {
-                        ($row:expr, $offset:expr) => {
-                            for n in 0..7 {
-                                let v = (n + $offset) % 12;
-                                if notes.contains(&v) {
-                                    colors[($row * 8 + n + 1) as usize] = colors::YELLOW;
-                                }
                             }
-                        };
-                    }
-                    bass!(3, 0);
-                    bass!(2, 5);
-                    bass!(1, 10);
-                    bass!(0, 15);
-
-                    color!(
-                        colors::RED => [11, 25]
-                    );
-                }
-                Keyboard::Waffletone => {
-                    let notes = state.scale.notes();
-                    for col in 0..7 {
-                        for row in 0..4 {
-                            let v = ((12 - row) + col * 3) % 12;
-                            if notes.contains(&v) {
-                                colors[(row * 8 + col + 1) as usize] = colors::YELLOW;
+                        }
+                        Keyboard::Bass | Keyboard::Waffletone => {
+                            if state.latched((col, row)) {
+                                colors::WHITE
+                            } else if state.incoming(col, row) {
+                                colors::MAGENTA
+                            } else if v == 0 {
+                                colors::RED
+                            } else if notes.contains(&v) {
+                                colors::YELLOW
+                            } else {
+                                colors::BLACK
                             }
                         }
-                    }
-                    color!(
-                        colors::RED => [1, 5, 26, 30]
-                    );
+                    };
                 }
             }
+
+            if state.dynamics.source == VelocitySource::Accent {
+                colors[ACCENT_PAD.into_index()] = if state.key_pressed(ACCENT_PAD) {
+                    colors::WHITE
+                } else {
+                    colors::PINK
+                };
+            }
+
+            if state.repeat.enabled && state.key_pressed((0, 3)) {
+                for i in 0..ARP_RATES.len() {
+                    colors[i + 1] = if i == state.repeat.rate as usize {
+                        colors::WHITE
+                    } else {
+                        hue(i as u8 * 32)
+                    };
+                }
+                colors[7] = colors::BLACK;
+            }
         }
         Mode::SelectRoot { hold } => {
             colors[0] = colors::BLUE;
@@ -360,29 +981,499 @@ fn update_colors(state: &mut State) {
                 };
             }
 
+            colors[0] = if state.sustain_cc {
+                colors::BLUE
+            } else {
+                colors::CYAN
+            };
             colors[24] = colors::YELLOW;
+            if state.repeat.enabled {
+                colors[3 + 8] = colors::ORANGE;
+            }
+            colors[4 + 2 * 8] = if state.harmony.above | state.harmony.below != 0 {
+                colors::RED
+            } else {
+                colors::PINK
+            };
+            if state.latch {
+                colors[5 + 2 * 8] = colors::ORANGE;
+            }
 
+            colors[8 + 4..8 + 6].copy_from_slice(&channel_colors(Some(state.channel)));
             colors[6 + 8] = hue(((state.velocity as f32 / 127f32) * 255.0) as u8);
             colors[7 + 8] = hue((((5 + state.velocity) as f32 / 127f32) * 255.0) as u8);
-            colors[6 + 2 * 8] =
-                hue(((state.octave as f32 / (1 + MAX_OCTAVE) as f32) * 255.0) as u8);
-            colors[7 + 2 * 8] =
-                hue((((1 + state.octave) as f32 / (1 + MAX_OCTAVE) as f32) * 255.0) as u8);
+            colors[2 * 8 + 6..2 * 8 + 8].copy_from_slice(&octave_colors(state));
             colors[6 + 3 * 8] = colors::CYAN;
             colors[7 + 3 * 8] = colors::BLUE;
 
-            macro_rules! keyboard {
-                ($i:expr, $k:expr) => {
-                    if state.keyboard == $k {
-                        colors[$i + 3 * 8] = colors::RED;
+            colors[3 * 8 + 1..3 * 8 + 6].copy_from_slice(&keyboard_colors(state));
+            for (i, mode) in [Mode::Sequencer, Mode::Drums, Mode::Euclid]
+                .into_iter()
+                .enumerate()
+            {
+                colors[i + 1 + 2 * 8] = if state.home == mode {
+                    colors::RED
+                } else {
+                    colors::PURPLE
+                };
+            }
+        }
+        Mode::Zones => {
+            colors[8] = colors::BLUE;
+            colors[16] = colors::PURPLE;
+
+            for col in 1..8u8 {
+                let z = state.zone_at(col);
+                if state.zones[z].start == col {
+                    colors[col as usize] = colors::WHITE;
+                }
+                colors[col as usize + 8] = if z == state.zone {
+                    colors::RED
+                } else {
+                    hue(z as u8 * 64)
+                };
+            }
+
+            colors[2 * 8 + 1..2 * 8 + 6].copy_from_slice(&keyboard_colors(state));
+            colors[2 * 8 + 6..2 * 8 + 8].copy_from_slice(&octave_colors(state));
+
+            colors[3 * 8 + 6..3 * 8 + 8]
+                .copy_from_slice(&channel_colors(state.zones[state.zone].channel));
+
+            colors[1 + 3 * 8] = if state.home == Mode::Host {
+                colors::ORANGE
+            } else {
+                colors::PURPLE
+            };
+            colors[3 * 8 + 3..3 * 8 + 5].copy_from_slice(&channel_colors(Some(state.host.channel)));
+        }
+        Mode::Harmony => {
+            colors[8] = colors::BLUE;
+
+            for i in 0..HARMONY_STEPS.len() {
+                colors[i + 1] = if state.harmony.above & (1 << i) != 0 {
+                    colors::RED
+                } else {
+                    colors::LIME_GREEN
+                };
+                colors[i + 1 + 8] = if state.harmony.below & (1 << i) != 0 {
+                    colors::RED
+                } else {
+                    colors::CYAN
+                };
+            }
+            colors[7 + 3 * 8] = colors::RED;
+
+            colors[1 + 2 * 8] = if state.home == Mode::Faders {
+                colors::RED
+            } else {
+                colors::PURPLE
+            };
+            colors[2 + 2 * 8] = if state.tilt.enabled {
+                colors::RED
+            } else {
+                colors::PINK
+            };
+            colors[3 + 2 * 8] = colors::PINK;
+
+            colors[1 + 3 * 8] = if state.mono.enabled {
+                colors::RED
+            } else {
+                colors::LIME_GREEN
+            };
+            for i in 0..3 {
+                colors[i + 2 + 3 * 8] = if state.mono.priority as usize == i {
+                    colors::RED
+                } else {
+                    colors::YELLOW
+                };
+            }
+            colors[5 + 3 * 8] = if state.mono.legato {
+                colors::RED
+            } else {
+                colors::PURPLE
+            };
+        }
+        Mode::Velocity => {
+            colors[8] = colors::BLUE;
+
+            for i in 0..4 {
+                colors[i + 1] = if state.dynamics.source as usize == i {
+                    colors::RED
+                } else {
+                    colors::LIME_GREEN
+                };
+            }
+            // each curve shows what a medium velocity turns into
+            for i in 0..3 {
+                let curve = Curve::from(i as u8);
+                colors[i + 1 + 8] = if state.dynamics.curve == curve {
+                    colors::RED
+                } else {
+                    hue(((curve.apply(64) as f32 / 127f32) * 255.0) as u8)
+                };
+            }
+            colors[1 + 2 * 8] = hue(state.dynamics.spread * 2);
+            colors[2 + 2 * 8] = hue((state.dynamics.spread * 2).saturating_add(8));
+            colors[6 + 2 * 8] = hue(((state.velocity as f32 / 127f32) * 255.0) as u8);
+            colors[7 + 2 * 8] = hue((((5 + state.velocity) as f32 / 127f32) * 255.0) as u8);
+        }
+        Mode::Tilt => {
+            let tilt = &state.tilt;
+            colors[8] = colors::BLUE;
+
+            colors[1] = if tilt.enabled {
+                colors::RED
+            } else {
+                colors::LIME_GREEN
+            };
+            colors[2] = colors::WHITE;
+            for i in 0..4 {
+                colors[i + 4] = if i * 2 == tilt.motion.smoothing as usize {
+                    colors::RED
+                } else {
+                    colors::CYAN
+                };
+            }
+
+            for (axis, tilt) in tilt.axes.iter().enumerate() {
+                let row = (axis + 1) * 8;
+                for i in 0..3 {
+                    colors[row + i + 1] = if tilt.target as usize == i {
+                        colors::RED
+                    } else {
+                        colors::GREEN
+                    };
+                }
+                colors[row + 6] = hue(tilt.cc * 2);
+                colors[row + 7] = hue((tilt.cc * 2).saturating_add(2));
+            }
+
+            let deadzone = tilt.motion.deadzone as u8;
+            colors[1 + 3 * 8] = hue(deadzone.saturating_mul(4));
+            colors[2 + 3 * 8] = hue(deadzone.saturating_mul(4).saturating_add(16));
+            let range = (tilt.motion.range / 2) as u8;
+            colors[4 + 3 * 8] = hue(range);
+            colors[5 + 3 * 8] = hue(range.saturating_add(8));
+
+            colors[6 + 3 * 8] = if state.strike.enabled {
+                colors::RED
+            } else {
+                colors::LIME_GREEN
+            };
+            colors[7 + 3 * 8] = colors::PURPLE;
+        }
+        Mode::Strike => {
+            colors[8] = colors::BLUE;
+            colors[24] = colors::RED;
+
+            // pads light up white while held, and otherwise show their gain once calibrated
+            for col in 1..8u8 {
+                for row in 0..4u8 {
+                    let gain = state.strike.gains[row as usize][col as usize - 1];
+                    colors[(col, row).into_index()] = if state.key_pressed((col, row)) {
+                        colors::WHITE
+                    } else if gain == UNITY_GAIN {
+                        colors::BLACK
+                    } else {
+                        hue(gain)
+                    };
+                }
+            }
+        }
+        Mode::Arp => {
+            colors[8] = colors::BLUE;
+
+            colors[1] = if state.arp.enabled {
+                colors::RED
+            } else {
+                colors::LIME_GREEN
+            };
+            colors[2] = if state.arp.latch {
+                colors::RED
+            } else {
+                colors::LIME_GREEN
+            };
+            for i in 0..4 {
+                colors[i + 4] = if i < state.arp.gate as usize {
+                    colors::YELLOW
+                } else {
+                    colors::BLACK
+                };
+            }
+            for i in 0..6 {
+                colors[i + 1 + 8] = if state.arp.mode as usize == i {
+                    colors::RED
+                } else {
+                    colors::GREEN
+                };
+                colors[i + 1 + 2 * 8] = if state.arp.rate as usize == i {
+                    colors::RED
+                } else {
+                    colors::CYAN
+                };
+            }
+            for i in 0..4 {
+                colors[i + 1 + 3 * 8] = if i < state.arp.octaves as usize {
+                    colors::PURPLE
+                } else {
+                    colors::BLACK
+                };
+            }
+
+            let bpm = ((state.clock.bpm - MIN_BPM) as f32 / (MAX_BPM - MIN_BPM) as f32) * 255.0;
+            colors[6 + 3 * 8] = hue(bpm as u8);
+            colors[7 + 3 * 8] = hue((bpm as u8).saturating_add(5));
+        }
+        Mode::Tempo => {
+            colors[0] = if state.clock.output {
+                colors::RED
+            } else {
+                colors::LIME_GREEN
+            };
+            colors[8] = colors::BLUE;
+            colors[16] = colors::CYAN;
+            colors[24] = sync_color(state);
+
+            // last two digits, with the hundreds shown by the color
+            let bpm = state.clock.bpm;
+            let color = match bpm / 100 {
+                0 => colors::GREEN,
+                1 => colors::YELLOW,
+                _ => colors::RED,
+            };
+            for (d, offset) in [((bpm / 10 % 10) as u8, 1), ((bpm % 10) as u8, 5)] {
+                let digit = letters::digit(d);
+                for i in 0..3 {
+                    for j in 0..4 {
+                        if digit[i + j * 3] == 1 {
+                            colors[offset + i + j * 8] = color;
+                        }
                     }
+                }
+            }
+
+            colors[4] = if state.clock.on_beat() {
+                colors::WHITE
+            } else {
+                colors::BLACK
+            };
+            colors[4 + 8] = colors::PURPLE;
+            colors[4 + 2 * 8] = colors::PINK;
+        }
+        Mode::Sequencer => {
+            let seq = &state.sequencer;
+            let color = match seq.param {
+                SeqParam::Note => colors::YELLOW,
+                SeqParam::Velocity => colors::RED,
+                SeqParam::Gate => colors::CYAN,
+                SeqParam::Probability => colors::GREEN,
+            };
+
+            for col in 0..8 {
+                let step = seq.step_at(col);
+                let playhead = seq.position == Some(step as u8);
+                for row in 0..4 {
+                    let lit = match seq.param {
+                        SeqParam::Note => seq.steps[step].degree == Some(seq.degree_at(row)),
+                        _ => 4 - row <= seq.level(step),
+                    };
+                    colors[(col, row).into_index()] = match (lit, playhead) {
+                        (true, true) => colors::WHITE,
+                        (true, false) => color,
+                        (false, true) => colors::BLUE,
+                        (false, false) => colors::BLACK,
+                    };
+                }
+            }
+        }
+        Mode::SequencerMenu => {
+            let seq = &state.sequencer;
+            colors[16] = colors::BLUE;
+
+            for i in 0..4 {
+                colors[i + 1] = if seq.param as usize == i {
+                    colors::RED
+                } else {
+                    colors::GREEN
                 };
             }
-            keyboard!(1, Keyboard::Scale);
-            keyboard!(2, Keyboard::Chords);
-            keyboard!(3, Keyboard::Sampler);
-            keyboard!(4, Keyboard::Bass);
-            keyboard!(5, Keyboard::Waffletone);
+            for (i, length) in [16, 32, 64].into_iter().enumerate() {
+                colors[i + 5] = if seq.length == length {
+                    colors::RED
+                } else {
+                    colors::CYAN
+                };
+            }
+
+            let pages = seq.length / PAGE_STEPS as u8;
+            for i in 0..4u8 {
+                for (row, page) in [(1, i), (2, i + 4)] {
+                    colors[(i + 1, row).into_index()] = if page == seq.page {
+                        colors::RED
+                    } else if Some(page) == seq.position.map(|p| p / PAGE_STEPS as u8) {
+                        colors::WHITE
+                    } else if page < pages {
+                        colors::LIME_GREEN
+                    } else {
+                        colors::BLACK
+                    };
+                }
+            }
+
+            colors[6 + 8] = colors::PURPLE;
+            colors[7 + 8] = colors::PINK;
+            colors[6 + 2 * 8] = if seq.follow {
+                colors::YELLOW
+            } else {
+                colors::BLACK
+            };
+            colors[7 + 3 * 8] = colors::RED;
+        }
+        Mode::Drums => {
+            let drums = &state.drums;
+            colors[0] = colors::WHITE;
+            colors[8] = colors::BLUE;
+            colors[16] = colors::RED;
+            colors[24] = colors::YELLOW;
+
+            let lane = drums.lane;
+            let lanes = [(0, 0), (0, 2), (0, 3)]
+                .iter()
+                .any(|&pad| state.key_pressed(pad));
+            if lanes {
+                for l in 0..LANES {
+                    colors[step_pad(l as u32).into_index()] =
+                        if drums.playing & (1 << l) != 0 || l == lane {
+                            colors::WHITE
+                        } else if drums.lanes[l].mute {
+                            colors::RED
+                        } else if drums.lanes[l].solo {
+                            colors::YELLOW
+                        } else {
+                            hue(l as u8 * 32)
+                        };
+                }
+            } else {
+                for step in 0..DRUM_STEPS {
+                    let playhead = drums.position == Some(step as u8);
+                    colors[step_pad(step).into_index()] = match (drums.step(lane, step), playhead) {
+                        (true, true) => colors::WHITE,
+                        (true, false) => hue(lane as u8 * 32),
+                        (false, true) => colors::BLUE,
+                        (false, false) => colors::BLACK,
+                    };
+                }
+            }
+            if state.key_pressed((0, 0)) {
+                let note = drums.lanes[lane].note;
+                colors[1 + 2 * 8] = hue(note * 2);
+                colors[2 + 2 * 8] = hue((note * 2).saturating_add(2));
+                colors[3 + 2 * 8..5 + 2 * 8]
+                    .copy_from_slice(&channel_colors(Some(drums.lanes[lane].channel)));
+            }
+
+            for p in 0..PATTERNS {
+                colors[(7, p as u8).into_index()] = if p == drums.pattern {
+                    colors::GREEN
+                } else if Some(p) == drums.queued {
+                    colors::YELLOW
+                } else if (drums.chain.0..=drums.chain.1).contains(&p) {
+                    colors::CYAN
+                } else if drums.patterns[p].iter().any(|&steps| steps != 0) {
+                    colors::LIME_GREEN
+                } else {
+                    colors::BLACK
+                };
+            }
+        }
+        Mode::Euclid => {
+            let euclid = &state.euclid;
+            colors[8] = colors::BLUE;
+            colors[16] = colors::WHITE;
+            colors[24] = colors::YELLOW;
+
+            let edit = state.key_pressed((0, 2));
+            let pick_note = state.key_pressed((0, 3));
+            for r in 0..RHYTHMS {
+                let rhythm = euclid.rhythms[r];
+                let row = 8 * r;
+                if edit {
+                    colors[row + 1] = hue(rhythm.steps.saturating_mul(16));
+                    colors[row + 2] = hue(rhythm.steps.saturating_mul(16).saturating_add(16));
+                    colors[row + 3] = hue(rhythm.pulses.saturating_mul(16));
+                    colors[row + 4] = hue(rhythm.pulses.saturating_mul(16).saturating_add(16));
+                    colors[row + 5] = hue(rhythm.rotation.saturating_mul(16));
+                    colors[row + 6] = hue(rhythm.rotation.saturating_mul(16).saturating_add(16));
+                    colors[row + 7] = if rhythm.mute {
+                        colors::RED
+                    } else {
+                        colors::GREEN
+                    };
+                } else if pick_note {
+                    for col in 1..8u8 {
+                        colors[row + col as usize] = if rhythm.degree % 7 == col - 1 {
+                            if rhythm.degree >= 7 {
+                                colors::WHITE
+                            } else {
+                                colors::RED
+                            }
+                        } else {
+                            colors::LIME_GREEN
+                        };
+                    }
+                } else {
+                    // the pattern scrolls to the left, with the playhead on the first column
+                    let step = euclid.step(r).unwrap_or(0);
+                    for col in 1..8 {
+                        let step = (step + col - 1) % rhythm.steps as u32;
+                        colors[row + col as usize] = match (rhythm.pulse(step), col == 1) {
+                            (true, _) if rhythm.mute => colors::RED,
+                            (true, true) if euclid.position.is_some() => colors::WHITE,
+                            (true, _) => hue(r as u8 * 64),
+                            // mark where the pattern starts over
+                            (false, _) if step == 0 => colors::BLUE,
+                            (false, _) => colors::BLACK,
+                        };
+                    }
+                }
+            }
+        }
+        Mode::Host => {
+            for (color, &index) in colors.iter_mut().zip(&state.host.colors) {
+                *color = palette(index);
+            }
+            colors[8] = colors::BLUE;
+        }
+        Mode::Faders => {
+            colors[0] = colors::GREEN;
+            colors[8] = colors::BLUE;
+            colors[16] = colors::PURPLE;
+            colors[24] = colors::YELLOW;
+
+            let bind = state.key_pressed((0, 2));
+            for (f, fader) in state.faders.iter().enumerate() {
+                let col = f + 1;
+                if bind {
+                    colors[col] = hue(fader.cc * 2);
+                    colors[col + 8] = hue((fader.cc * 2).saturating_add(2));
+                    let [down, up] = channel_colors(fader.channel);
+                    colors[col + 2 * 8] = down;
+                    colors[col + 3 * 8] = up;
+                    continue;
+                }
+
+                // the bar fills up from the bottom row, in the same hues as velocity
+                let color = hue(((fader.value as f32 / 127f32) * 255.0) as u8);
+                for row in 0..4 {
+                    let level = FADER_LEVELS[3 - row];
+                    colors[col + row * 8] = if fader.value >= level && fader.value > 0 {
+                        color
+                    } else {
+                        colors::BLACK
+                    };
+                }
+            }
         }
     }
 
@@ -393,6 +1484,44 @@ fn update_colors(state: &mut State) {
         .unwrap();
 }
 
+/// Blinks green in time when following an external clock,
+/// red when sync is on but there's no clock, and black when sync is off
+fn sync_color(state: &mut State) -> RGB8 {
+    if !state.clock.sync {
+        colors::BLACK
+    } else if !state.synced() {
+        colors::RED
+    } else if state.clock.on_beat() {
+        colors::GREEN
+    } else {
+        colors::BLACK
+    }
+}
+
+/// Colors for the `-1` and `+1` octave buttons of the selected zone
+fn octave_colors(state: &State) -> [RGB8; 2] {
+    let octave = state.zones[state.zone].octave;
+    [
+        hue(((octave as f32 / (1 + MAX_OCTAVE) as f32) * 255.0) as u8),
+        hue((((1 + octave) as f32 / (1 + MAX_OCTAVE) as f32) * 255.0) as u8),
+    ]
+}
+
+/// Colors for the `-1` and `+1` channel buttons, white when following the global channel
+fn channel_colors(channel: Option<u8>) -> [RGB8; 2] {
+    match channel {
+        Some(c) => [hue(c * 16), hue((c * 16).saturating_add(16))],
+        None => [colors::WHITE; 2],
+    }
+}
+
+/// Colors for the keyboard select buttons, highlighting the selected zone's keyboard
+fn keyboard_colors(state: &State) -> [RGB8; 5] {
+    let mut colors = [colors::BLACK; 5];
+    colors[state.zones[state.zone].keyboard as usize] = colors::RED;
+    colors
+}
+
 fn hue(hue: u8) -> RGB8 {
     hsv2rgb(Hsv {
         hue,
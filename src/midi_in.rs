@@ -0,0 +1,123 @@
+use crate::{
+    clock::PPQN,
+    state::State,
+    usb::{read_packet, send_sysex},
+};
+
+/// A message received from the host
+#[derive(Copy, Clone)]
+pub enum Message {
+    TimingClock,
+    Start,
+    Continue,
+    Stop,
+    /// position in sixteenth notes since the start of the song
+    SongPosition(u16),
+    /// note on with velocity 0 is parsed as note off
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+    },
+    ControlChange {
+        channel: u8,
+        control: u8,
+        value: u8,
+    },
+}
+
+impl Message {
+    /// Parses a USB MIDI event packet. Returns `None` for messages we don't handle
+    pub fn parse(packet: [u8; 4]) -> Option<Self> {
+        let [header, status, a, b] = packet;
+
+        // the low nibble of the header is the code index number, which tells the kind of packet
+        let channel = status & 0x0f;
+        Some(match (header & 0x0f, status & 0xf0) {
+            (0x9, 0x90) if b > 0 => Message::NoteOn {
+                channel,
+                note: a,
+                velocity: b,
+            },
+            (0x8, 0x80) | (0x9, 0x90) => Message::NoteOff { channel, note: a },
+            (0xb, 0xb0) => Message::ControlChange {
+                channel,
+                control: a,
+                value: b,
+            },
+            (0x3, 0xf0) if status == 0xf2 => Message::SongPosition(a as u16 | (b as u16) << 7),
+            (0xf, 0xf0) => match status {
+                0xf8 => Message::TimingClock,
+                0xfa => Message::Start,
+                0xfb => Message::Continue,
+                0xfc => Message::Stop,
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl State {
+    /// Handles every message received since the last call
+    pub fn read_midi(&mut self) {
+        while let Some((cycle_count, packet)) = read_packet() {
+            // sysex is split over several packets, so it's put back together before handling it
+            if let 0x4..=0x7 = packet[0] & 0x0f {
+                if let Some(len) = self.sysex.push(packet) {
+                    let message = self.sysex.buf;
+                    self.handle_sysex(&message[..len], send_sysex);
+                }
+                continue;
+            }
+
+            let Some(message) = Message::parse(packet) else {
+                continue;
+            };
+            let time = self.board.micros_at(cycle_count);
+
+            match message {
+                Message::TimingClock => self.clock.external_tick(time),
+                Message::Start if self.clock.sync => {
+                    self.clock.running = true;
+                    self.clock.position = 0;
+                    self.clock.beat = 0;
+                }
+                Message::Continue if self.clock.sync => self.clock.running = true,
+                Message::Stop if self.clock.sync => self.clock.running = false,
+                Message::SongPosition(sixteenths) if self.clock.sync => {
+                    let position = sixteenths as u32 * PPQN / 4;
+                    self.clock.position = position;
+                    self.clock.beat = position % PPQN;
+                }
+                // the velocity or value is the palette index, like a launchpad in programmer mode
+                Message::NoteOn {
+                    channel,
+                    note,
+                    velocity,
+                } if channel == self.host.channel => self.set_host_color(note, velocity),
+                Message::NoteOff { channel, note } if channel == self.host.channel => {
+                    self.set_host_color(note, 0)
+                }
+                Message::ControlChange {
+                    channel,
+                    control,
+                    value,
+                } if channel == self.host.channel => self.set_host_color(control, value),
+                // notes on any other channel are the host playing, and light the pads that play them
+                Message::NoteOn { channel, note, .. } => self.set_incoming(channel, note, true),
+                Message::NoteOff { channel, note } => self.set_incoming(channel, note, false),
+                Message::ControlChange {
+                    channel,
+                    control: 120 | 123,
+                    ..
+                } => self.host.incoming[channel as usize] = 0,
+                _ => {}
+            }
+        }
+    }
+}
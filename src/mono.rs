@@ -0,0 +1,131 @@
+use crate::state::State;
+
+/// max number of held notes mono mode keeps track of
+const MAX_MONO_NOTES: usize = 16;
+
+/// Which held note plays when several are held at once
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Priority {
+    Last,
+    Low,
+    High,
+}
+
+impl Priority {
+    pub const fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Last,
+            1 => Self::Low,
+            2 => Self::High,
+            _ => panic!("number is not in 0..3"),
+        }
+    }
+}
+
+/// Held notes of a single channel
+#[derive(Copy, Clone)]
+struct Voice {
+    /// held notes as `(note, velocity)`, in the order they were played,
+    /// so going back to one plays it as hard as it was played
+    stack: [(u8, u8); MAX_MONO_NOTES],
+    len: usize,
+    /// the note sounding right now
+    sounding: Option<u8>,
+}
+
+impl Voice {
+    const fn new() -> Self {
+        Self {
+            stack: [(0, 0); MAX_MONO_NOTES],
+            len: 0,
+            sounding: None,
+        }
+    }
+
+    /// Returns the held note that should be sounding
+    fn target(&self, priority: Priority) -> Option<(u8, u8)> {
+        let held = self.stack[..self.len].iter().copied();
+        match priority {
+            Priority::Last => held.last(),
+            Priority::Low => held.min_by_key(|&(note, _)| note),
+            Priority::High => held.max_by_key(|&(note, _)| note),
+        }
+    }
+}
+
+/// Plays a single note at a time on each channel, like a monosynth per zone
+pub struct Mono {
+    pub enabled: bool,
+    pub priority: Priority,
+    /// whether moving between held notes slides without retriggering
+    pub legato: bool,
+
+    /// held notes of each channel, so zones on different channels don't cut each other off
+    voices: [Voice; 16],
+}
+
+impl Mono {
+    pub const fn new() -> Self {
+        Self {
+            enabled: false,
+            priority: Priority::Last,
+            legato: false,
+
+            voices: [Voice::new(); 16],
+        }
+    }
+
+    /// Forgets every held note and the sounding ones, which should be turned off by the caller
+    pub fn clear(&mut self) {
+        self.voices = [Voice::new(); 16];
+    }
+}
+
+impl State {
+    /// Adds or removes a held note, playing whichever one of its channel has priority
+    pub fn mono_note(&mut self, channel: u8, note: u8, on: bool) {
+        let priority = self.mono.priority;
+        let velocity = self.note_velocity();
+        let voice = &mut self.mono.voices[channel as usize & 0x0f];
+        if let Some(pos) = voice.stack[..voice.len]
+            .iter()
+            .position(|&(n, _)| n == note)
+        {
+            voice.stack.copy_within(pos + 1..voice.len, pos);
+            voice.len -= 1;
+        }
+        if on {
+            // drop the oldest note when the stack is full
+            if voice.len == MAX_MONO_NOTES {
+                voice.stack.copy_within(1.., 0);
+                voice.len -= 1;
+            }
+            voice.stack[voice.len] = (note, velocity);
+            voice.len += 1;
+        }
+
+        let target = voice.target(priority);
+        let sounding = voice.sounding;
+        if target.map(|(note, _)| note) == sounding {
+            return;
+        }
+        voice.sounding = target.map(|(note, _)| note);
+
+        // with legato the new note starts before the old one ends, so the synth slides to it
+        if self.mono.legato {
+            if let Some((note, velocity)) = target {
+                self.send_note(channel, note, velocity, true);
+            }
+            if let Some(note) = sounding {
+                self.send_midi(channel, note, false);
+            }
+        } else {
+            if let Some(note) = sounding {
+                self.send_midi(channel, note, false);
+            }
+            if let Some((note, velocity)) = target {
+                self.send_note(channel, note, velocity, true);
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Note {
     C,
     Cs,
@@ -31,9 +31,27 @@ impl Note {
             Note::B => false,
         }
     }
+
+    pub const fn from(i: u8) -> Self {
+        match i {
+            0 => Self::C,
+            1 => Self::Cs,
+            2 => Self::D,
+            3 => Self::Ds,
+            4 => Self::E,
+            5 => Self::F,
+            6 => Self::Fs,
+            7 => Self::G,
+            8 => Self::Gs,
+            9 => Self::A,
+            10 => Self::As,
+            11 => Self::B,
+            _ => panic!("number is not in 0..12"),
+        }
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Scale {
     Ionian,
     Dorian,
@@ -61,6 +79,18 @@ impl Scale {
             Scale::Locrian => [0, 1, 3, 5, 6, 8, 10],
         }
     }
+    /// Returns the semitones between degree `i` and the degree `steps` above it
+    pub const fn interval(self, i: u8, steps: u8) -> u8 {
+        let j = i + steps;
+        self.get(j % 7) + 12 * (j / 7) - self.get(i)
+    }
+    /// Returns the degree of the note `n` semitones above the root, if it's in the scale
+    pub fn degree(self, n: u8) -> Option<u8> {
+        self.notes()
+            .iter()
+            .position(|&note| note == n % 12)
+            .map(|d| d as u8)
+    }
     // pub fn chords(self) -> [Chord; 7] {
     //     let mut chords = [
     //         Chord::Major,
@@ -0,0 +1,95 @@
+use crate::{
+    arp::ARP_RATES,
+    clock::PPQN,
+    state::{Keyboard, Mode, State},
+};
+
+/// Retriggers the notes of held pads in time, like the note repeat of a drum machine
+pub struct Repeat {
+    pub enabled: bool,
+    /// index into `ARP_RATES`
+    pub rate: u8,
+
+    /// step the last retrigger happened on, counted since power on
+    step: Option<u32>,
+    /// pads retriggered on the current step as a bitmask of key indices, to be turned off halfway through it
+    playing: u32,
+}
+
+impl Repeat {
+    pub const fn new() -> Self {
+        Self {
+            enabled: false,
+            rate: 3,
+
+            step: None,
+            playing: 0,
+        }
+    }
+}
+
+impl Keyboard {
+    /// Returns whether note repeat retriggers the pads of this keyboard
+    pub fn repeats(self) -> bool {
+        matches!(self, Keyboard::Scale | Keyboard::Sampler | Keyboard::Bass)
+    }
+}
+
+impl State {
+    /// Retriggers the notes of every held pad, on steps lined up with the beat.
+    /// they go through the arpeggiator, mono mode and the looper like the pads do, at the velocity the pad was hit with
+    pub fn update_repeat(&mut self) {
+        // held pads don't retrigger while panicking
+        if !self.repeat.enabled || self.mode != Mode::Normal || self.column.chord {
+            self.repeat_notes_off();
+            self.repeat.step = None;
+            return;
+        }
+
+        let rate = ARP_RATES[self.repeat.rate as usize];
+        // the beat wraps every quarter note, too soon for the slowest rate to tell steps apart
+        let tick = self
+            .clock
+            .beats
+            .wrapping_mul(PPQN)
+            .wrapping_add(self.clock.beat);
+        let step = tick / rate;
+        if tick % rate >= rate / 2 {
+            self.repeat_notes_off();
+        }
+        if self.repeat.step == Some(step) {
+            return;
+        }
+        self.repeat.step = Some(step);
+        self.repeat_notes_off();
+
+        for col in 1..8 {
+            if !self.zones[self.zone_at(col)].keyboard.repeats() {
+                continue;
+            }
+            for row in 0..4 {
+                // pads pressed just now were already played by the keyboard
+                if !self.key_pressed((col, row)) || self.key_just_pressed((col, row)) {
+                    continue;
+                }
+
+                // the first hit is still sounding, so cut it before retriggering
+                if self.replay_pad(col, row, false) {
+                    self.replay_pad(col, row, true);
+                    self.repeat.playing |= 1 << (col + row * 8);
+                }
+            }
+        }
+    }
+
+    fn repeat_notes_off(&mut self) {
+        for i in 0..32u8 {
+            // pads let go of since then were already turned off by the keyboard
+            let (col, row) = (i % 8, i / 8);
+            if self.repeat.playing & 1 << i != 0 && self.key_pressed(i) {
+                self.replay_pad(col, row, false);
+            }
+        }
+        self.repeat.playing = 0;
+    }
+}
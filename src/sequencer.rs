@@ -0,0 +1,188 @@
+use crate::{
+    clock::PPQN,
+    state::{Mode, State},
+};
+
+pub const MAX_STEPS: usize = 64;
+/// steps shown at once, one per column
+pub const PAGE_STEPS: usize = 8;
+/// steps are sixteenth notes
+const STEP_TICKS: u32 = PPQN / 4;
+/// how many scale degrees the sequencer can reach, four octaves
+pub const MAX_DEGREE: u8 = 7 * 4;
+
+#[derive(Copy, Clone)]
+pub struct Step {
+    /// scale degree counted from the root of the zone's octave, `None` for a rest
+    pub degree: Option<u8>,
+    pub velocity: u8,
+    /// how long the note lasts, in quarters of a step, from 1 to 4
+    pub gate: u8,
+    /// chance of the step playing, in quarters, from 1 to 4
+    pub probability: u8,
+}
+
+impl Step {
+    const fn new() -> Self {
+        Self {
+            degree: None,
+            velocity: 95,
+            gate: 2,
+            probability: 4,
+        }
+    }
+}
+
+/// What the grid edits while in sequencer mode
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum SeqParam {
+    Note,
+    Velocity,
+    Gate,
+    Probability,
+}
+
+impl SeqParam {
+    pub const fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Note,
+            1 => Self::Velocity,
+            2 => Self::Gate,
+            3 => Self::Probability,
+            _ => panic!("number is not in 0..4"),
+        }
+    }
+}
+
+pub struct Sequencer {
+    pub steps: [Step; MAX_STEPS],
+    /// 16, 32 or 64
+    pub length: u8,
+    pub param: SeqParam,
+    /// page shown on the grid
+    pub page: u8,
+    /// whether the page follows the playhead
+    pub follow: bool,
+    /// lowest scale degree shown on the grid, on the bottom row
+    pub view: u8,
+
+    /// step the playhead is on, `None` while stopped
+    pub position: Option<u8>,
+    /// note of the current step, while it's sounding
+    playing: Option<(u8, u8)>,
+    /// index and previous value of the step each control column pad edited,
+    /// put back if the pad is held long enough to do its action instead
+    pub undo: [Option<(usize, Step)>; 4],
+}
+
+impl Sequencer {
+    pub const fn new() -> Self {
+        Self {
+            steps: [Step::new(); MAX_STEPS],
+            length: 16,
+            param: SeqParam::Note,
+            page: 0,
+            follow: true,
+            view: 0,
+
+            position: None,
+            playing: None,
+            undo: [None; 4],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.steps = [Step::new(); MAX_STEPS];
+    }
+
+    /// Returns the index of the step shown on column `col`
+    pub fn step_at(&self, col: u8) -> usize {
+        self.page as usize * PAGE_STEPS + col as usize
+    }
+
+    /// Returns the scale degree shown on `row`
+    pub fn degree_at(&self, row: u8) -> u8 {
+        self.view + 3 - row
+    }
+
+    /// Edits the step on `col` using the pad on `row`, according to the selected param
+    pub fn edit(&mut self, col: u8, row: u8) {
+        let degree = self.degree_at(row);
+        let i = self.step_at(col);
+        let step = &mut self.steps[i];
+        // bottom row is the lowest value
+        let level = 4 - row;
+        match self.param {
+            SeqParam::Note if step.degree == Some(degree) => step.degree = None,
+            SeqParam::Note => step.degree = Some(degree),
+            SeqParam::Velocity => step.velocity = level * 32 - 1,
+            SeqParam::Gate => step.gate = level,
+            SeqParam::Probability => step.probability = level,
+        }
+    }
+
+    /// Returns the value of `param` for `step`, from 1 to 4
+    pub fn level(&self, step: usize) -> u8 {
+        let step = self.steps[step];
+        match self.param {
+            SeqParam::Note => 0,
+            SeqParam::Velocity => (step.velocity + 1) / 32,
+            SeqParam::Gate => step.gate,
+            SeqParam::Probability => step.probability,
+        }
+    }
+}
+
+impl State {
+    /// Returns the note played for `degree`, following the current root and scale
+    /// so transposing moves the whole pattern diatonically
+    pub fn degree_note(&self, degree: u8) -> u8 {
+        let octave = self.zones[self.zone].octave + degree / 7;
+        octave * 12 + self.root as u8 + self.scale.get(degree % 7)
+    }
+
+    /// Plays the sequencer, following the transport. It only plays while it's the home mode
+    pub fn update_sequencer(&mut self) {
+        if !self.clock.running || self.home != Mode::Sequencer {
+            self.sequencer_note_off();
+            self.sequencer.position = None;
+            return;
+        }
+
+        let position = self.clock.position / STEP_TICKS % self.sequencer.length as u32;
+        let tick = self.clock.position % STEP_TICKS;
+        let step = self.sequencer.steps[position as usize];
+
+        if tick >= (STEP_TICKS * step.gate as u32 / 4).max(1) {
+            self.sequencer_note_off();
+        }
+
+        if self.sequencer.position == Some(position as u8) {
+            return;
+        }
+        self.sequencer.position = Some(position as u8);
+        self.sequencer_note_off();
+
+        if self.sequencer.follow {
+            self.sequencer.page = position as u8 / PAGE_STEPS as u8;
+        }
+
+        let Some(degree) = step.degree else {
+            return;
+        };
+        if self.random() % 4 >= step.probability as u32 {
+            return;
+        }
+
+        let channel = self.zone_channel(self.zone);
+        let note = self.degree_note(degree);
+        self.send_note(channel, note, step.velocity, true);
+        self.sequencer.playing = Some((channel, note));
+    }
+
+    fn sequencer_note_off(&mut self) {
+        if let Some((channel, note)) = self.sequencer.playing.take() {
+            self.send_midi(channel, note, false);
+        }
+    }
+}
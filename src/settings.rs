@@ -0,0 +1,567 @@
+use crate::{
+    arp::{ArpMode, ARP_RATES},
+    clock::{MAX_BPM, MIN_BPM},
+    drums::LANES,
+    dynamics::{Curve, VelocitySource, MAX_SPREAD},
+    euclid::{MAX_EUCLID_DEGREE, MAX_EUCLID_STEPS},
+    mono::Priority,
+    music_theory::{Note, Scale},
+    sequencer::{MAX_DEGREE, PAGE_STEPS},
+    state::{Keyboard, State, MAX_OCTAVE, MAX_ZONES},
+    tilt::{TiltTarget, MAX_TILT_DEADZONE, MAX_TILT_RANGE, MIN_TILT_RANGE},
+};
+
+/// first byte of the stored settings, so we don't load garbage from an empty flash
+const MAGIC: u8 = 0xa5;
+/// bumped whenever the meaning of a stored value changes
+const VERSION: u8 = 2;
+
+/// max number of bytes the settings take when stored, a multiple of 4 so slots stay word aligned
+pub const SETTINGS_LEN: usize = 896;
+/// number of presets stored after the current settings
+pub const PRESETS: usize = 8;
+/// bytes before the first setting: the magic, the version and the count
+const SETTINGS_HEADER_LEN: usize = 4;
+/// bytes each setting takes, its id and its value
+const SETTING_LEN: usize = 3;
+
+/// Returns how many bytes the settings at the start of `buf` take, or `None` if it doesn't hold settings
+pub fn settings_len(buf: &[u8]) -> Option<usize> {
+    let [MAGIC, VERSION, count_hi, count_lo, ..] = *buf else {
+        return None;
+    };
+    Some(SETTINGS_HEADER_LEN + SETTING_LEN * u16::from_be_bytes([count_hi, count_lo]) as usize)
+}
+
+/// A setting that fits in a single byte
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Param {
+    Root,
+    Scale,
+    Velocity,
+    Brightness,
+    Channel,
+    ZoneCount,
+    Bpm,
+    ArpEnabled,
+    ArpLatch,
+    ArpMode,
+    ArpRate,
+    ArpOctaves,
+    ArpGate,
+    ClockOutput,
+    ClockSync,
+    RepeatEnabled,
+    RepeatRate,
+    HarmonyAbove,
+    HarmonyBelow,
+    SustainCc,
+    MonoEnabled,
+    MonoPriority,
+    MonoLegato,
+    TiltEnabled,
+    TiltSmoothing,
+    TiltDeadzone,
+    TiltRange,
+    StrikeEnabled,
+    VelocitySource,
+    VelocityCurve,
+    VelocitySpread,
+    HostChannel,
+    Latch,
+    SeqLength,
+    DrumNote(usize),
+    DrumChannel(usize),
+    /// steps of a lane in a pattern, index `pattern * LANES + lane`
+    DrumSteps(usize),
+    EuclidSteps(usize),
+    EuclidPulses(usize),
+    EuclidRotation(usize),
+    EuclidDegree(usize),
+    FaderCc(usize),
+    FaderChannel(usize),
+    TiltTarget(usize),
+    TiltCc(usize),
+    TiltCenter(usize),
+    /// index of the pad, `row * 7 + col - 1`
+    StrikeGain(usize),
+    ZoneStart(usize),
+    ZoneKeyboard(usize),
+    ZoneOctave(usize),
+    ZoneChannel(usize),
+    /// `0` for a rest, otherwise the scale degree plus one
+    SeqNote(usize),
+    /// velocity, gate and probability of a step, two bits each counting from 0
+    SeqLevels(usize),
+}
+
+const GLOBAL_PARAMS: [Param; 34] = [
+    Param::Root,
+    Param::Scale,
+    Param::Velocity,
+    Param::Brightness,
+    Param::Channel,
+    Param::ZoneCount,
+    Param::Bpm,
+    Param::ArpEnabled,
+    Param::ArpLatch,
+    Param::ArpMode,
+    Param::ArpRate,
+    Param::ArpOctaves,
+    Param::ArpGate,
+    Param::ClockOutput,
+    Param::ClockSync,
+    Param::RepeatEnabled,
+    Param::RepeatRate,
+    Param::HarmonyAbove,
+    Param::HarmonyBelow,
+    Param::SustainCc,
+    Param::MonoEnabled,
+    Param::MonoPriority,
+    Param::MonoLegato,
+    Param::TiltEnabled,
+    Param::TiltSmoothing,
+    Param::TiltDeadzone,
+    Param::TiltRange,
+    Param::StrikeEnabled,
+    Param::VelocitySource,
+    Param::VelocityCurve,
+    Param::VelocitySpread,
+    Param::HostChannel,
+    Param::Latch,
+    Param::SeqLength,
+];
+const EUCLID_PARAMS: u16 = 4;
+const ZONE_PARAMS: u16 = 4;
+const TILT_PARAMS: u16 = 3;
+
+impl Param {
+    /// Returns every param, in the order they are stored
+    pub fn all() -> impl Iterator<Item = Param> {
+        GLOBAL_PARAMS.into_iter().chain(
+            (0x20..=0xff)
+                .chain(0x200..=0x27f)
+                .filter_map(Param::from_id),
+        )
+    }
+
+    /// Returns the id used to identify this param when stored
+    pub const fn id(self) -> u16 {
+        match self {
+            Param::Root => 0,
+            Param::Scale => 1,
+            Param::Velocity => 2,
+            Param::Brightness => 3,
+            Param::Channel => 4,
+            Param::ZoneCount => 5,
+            Param::Bpm => 6,
+            Param::ArpEnabled => 7,
+            Param::ArpLatch => 8,
+            Param::ArpMode => 9,
+            Param::ArpRate => 10,
+            Param::ArpOctaves => 11,
+            Param::ArpGate => 12,
+            Param::ClockOutput => 13,
+            Param::ClockSync => 14,
+            Param::RepeatEnabled => 15,
+            Param::RepeatRate => 16,
+            Param::HarmonyAbove => 17,
+            Param::HarmonyBelow => 18,
+            Param::SustainCc => 19,
+            Param::MonoEnabled => 20,
+            Param::MonoPriority => 21,
+            Param::MonoLegato => 22,
+            Param::TiltEnabled => 23,
+            Param::TiltSmoothing => 24,
+            Param::TiltDeadzone => 25,
+            Param::TiltRange => 26,
+            Param::StrikeEnabled => 27,
+            Param::VelocitySource => 28,
+            Param::VelocityCurve => 29,
+            Param::VelocitySpread => 30,
+            Param::HostChannel => 31,
+            // the ids below 0x100 ran out, so newer globals start there
+            Param::Latch => 0x100,
+            Param::SeqLength => 0x101,
+            Param::DrumNote(l) => 0x20 + l as u16 * 2,
+            Param::DrumChannel(l) => 0x20 + l as u16 * 2 + 1,
+            Param::DrumSteps(i) => 0xa0 + i as u16,
+            Param::EuclidSteps(r) => 0x30 + r as u16 * EUCLID_PARAMS,
+            Param::EuclidPulses(r) => 0x30 + r as u16 * EUCLID_PARAMS + 1,
+            Param::EuclidRotation(r) => 0x30 + r as u16 * EUCLID_PARAMS + 2,
+            Param::EuclidDegree(r) => 0x30 + r as u16 * EUCLID_PARAMS + 3,
+            Param::FaderCc(f) => 0x50 + f as u16 * 2,
+            Param::FaderChannel(f) => 0x50 + f as u16 * 2 + 1,
+            Param::TiltTarget(a) => 0x60 + a as u16 * TILT_PARAMS,
+            Param::TiltCc(a) => 0x60 + a as u16 * TILT_PARAMS + 1,
+            Param::TiltCenter(a) => 0x60 + a as u16 * TILT_PARAMS + 2,
+            Param::StrikeGain(p) => 0x80 + p as u16,
+            Param::ZoneStart(z) => 0x40 + z as u16 * ZONE_PARAMS,
+            Param::ZoneKeyboard(z) => 0x40 + z as u16 * ZONE_PARAMS + 1,
+            Param::ZoneOctave(z) => 0x40 + z as u16 * ZONE_PARAMS + 2,
+            Param::ZoneChannel(z) => 0x40 + z as u16 * ZONE_PARAMS + 3,
+            Param::SeqNote(i) => 0x200 + i as u16 * 2,
+            Param::SeqLevels(i) => 0x200 + i as u16 * 2 + 1,
+        }
+    }
+
+    pub const fn from_id(id: u16) -> Option<Param> {
+        Some(match id {
+            0 => Param::Root,
+            1 => Param::Scale,
+            2 => Param::Velocity,
+            3 => Param::Brightness,
+            4 => Param::Channel,
+            5 => Param::ZoneCount,
+            6 => Param::Bpm,
+            7 => Param::ArpEnabled,
+            8 => Param::ArpLatch,
+            9 => Param::ArpMode,
+            10 => Param::ArpRate,
+            11 => Param::ArpOctaves,
+            12 => Param::ArpGate,
+            13 => Param::ClockOutput,
+            14 => Param::ClockSync,
+            15 => Param::RepeatEnabled,
+            16 => Param::RepeatRate,
+            17 => Param::HarmonyAbove,
+            18 => Param::HarmonyBelow,
+            19 => Param::SustainCc,
+            20 => Param::MonoEnabled,
+            21 => Param::MonoPriority,
+            22 => Param::MonoLegato,
+            23 => Param::TiltEnabled,
+            24 => Param::TiltSmoothing,
+            25 => Param::TiltDeadzone,
+            26 => Param::TiltRange,
+            27 => Param::StrikeEnabled,
+            28 => Param::VelocitySource,
+            29 => Param::VelocityCurve,
+            30 => Param::VelocitySpread,
+            31 => Param::HostChannel,
+            0x100 => Param::Latch,
+            0x101 => Param::SeqLength,
+            0x20..=0x2f => {
+                let lane = (id as usize - 0x20) / 2;
+                if id & 1 == 0 {
+                    Param::DrumNote(lane)
+                } else {
+                    Param::DrumChannel(lane)
+                }
+            }
+            0x30..=0x3f => {
+                let r = ((id - 0x30) / EUCLID_PARAMS) as usize;
+                match (id - 0x30) % EUCLID_PARAMS {
+                    0 => Param::EuclidSteps(r),
+                    1 => Param::EuclidPulses(r),
+                    2 => Param::EuclidRotation(r),
+                    _ => Param::EuclidDegree(r),
+                }
+            }
+            0x40..=0x4f => {
+                let z = ((id - 0x40) / ZONE_PARAMS) as usize;
+                if z >= MAX_ZONES {
+                    return None;
+                }
+                match (id - 0x40) % ZONE_PARAMS {
+                    0 => Param::ZoneStart(z),
+                    1 => Param::ZoneKeyboard(z),
+                    2 => Param::ZoneOctave(z),
+                    _ => Param::ZoneChannel(z),
+                }
+            }
+            0x50..=0x5d => {
+                let f = (id as usize - 0x50) / 2;
+                if id & 1 == 0 {
+                    Param::FaderCc(f)
+                } else {
+                    Param::FaderChannel(f)
+                }
+            }
+            0x60..=0x65 => {
+                let a = ((id - 0x60) / TILT_PARAMS) as usize;
+                match (id - 0x60) % TILT_PARAMS {
+                    0 => Param::TiltTarget(a),
+                    1 => Param::TiltCc(a),
+                    _ => Param::TiltCenter(a),
+                }
+            }
+            0x80..=0x9b => Param::StrikeGain(id as usize - 0x80),
+            0xa0..=0xbf => Param::DrumSteps(id as usize - 0xa0),
+            0x200..=0x27f => {
+                let i = (id as usize - 0x200) / 2;
+                if id & 1 == 0 {
+                    Param::SeqNote(i)
+                } else {
+                    Param::SeqLevels(i)
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl State {
+    pub fn get(&self, param: Param) -> u8 {
+        match param {
+            Param::Root => self.root as u8,
+            Param::Scale => self.scale as u8,
+            Param::Velocity => self.velocity,
+            Param::Brightness => self.brightness,
+            Param::Channel => self.channel,
+            Param::ZoneCount => self.zone_count as u8,
+            // bpm doesn't fit in a byte, so it's stored as an offset from the minimum
+            Param::Bpm => (self.clock.bpm - MIN_BPM) as u8,
+            Param::ClockOutput => self.clock.output as u8,
+            Param::ClockSync => self.clock.sync as u8,
+            Param::ArpEnabled => self.arp.enabled as u8,
+            Param::ArpLatch => self.arp.latch as u8,
+            Param::ArpMode => self.arp.mode as u8,
+            Param::ArpRate => self.arp.rate,
+            Param::ArpOctaves => self.arp.octaves,
+            Param::ArpGate => self.arp.gate,
+            Param::RepeatEnabled => self.repeat.enabled as u8,
+            Param::RepeatRate => self.repeat.rate,
+            Param::HarmonyAbove => self.harmony.above,
+            Param::HarmonyBelow => self.harmony.below,
+            Param::SustainCc => self.sustain_cc as u8,
+            Param::MonoEnabled => self.mono.enabled as u8,
+            Param::MonoPriority => self.mono.priority as u8,
+            Param::MonoLegato => self.mono.legato as u8,
+            Param::TiltEnabled => self.tilt.enabled as u8,
+            Param::TiltSmoothing => self.tilt.motion.smoothing,
+            Param::TiltDeadzone => self.tilt.motion.deadzone as u8,
+            Param::TiltRange => self.tilt.motion.range as u8,
+            Param::StrikeEnabled => self.strike.enabled as u8,
+            Param::VelocitySource => self.dynamics.source as u8,
+            Param::VelocityCurve => self.dynamics.curve as u8,
+            Param::VelocitySpread => self.dynamics.spread,
+            Param::HostChannel => self.host.channel,
+            Param::Latch => self.latch as u8,
+            Param::SeqLength => self.sequencer.length,
+            Param::DrumNote(l) => self.drums.lanes[l].note,
+            Param::DrumChannel(l) => self.drums.lanes[l].channel,
+            Param::DrumSteps(i) => self.drums.patterns[i / LANES][i % LANES],
+            Param::EuclidSteps(r) => self.euclid.rhythms[r].steps,
+            Param::EuclidPulses(r) => self.euclid.rhythms[r].pulses,
+            Param::EuclidRotation(r) => self.euclid.rhythms[r].rotation,
+            Param::EuclidDegree(r) => self.euclid.rhythms[r].degree,
+            Param::FaderCc(f) => self.faders[f].cc,
+            // 16 means the fader follows the global channel
+            Param::FaderChannel(f) => self.faders[f].channel.unwrap_or(16),
+            Param::TiltTarget(a) => self.tilt.axes[a].target as u8,
+            Param::TiltCc(a) => self.tilt.axes[a].cc,
+            // the resting position is a few counts away from 0, so it fits in a signed byte
+            Param::TiltCenter(a) => self.tilt.motion.center()[a].clamp(-128, 127) as i8 as u8,
+            Param::StrikeGain(p) => self.strike.gains[p / 7][p % 7],
+            Param::ZoneStart(z) => self.zones[z].start,
+            Param::ZoneKeyboard(z) => self.zones[z].keyboard as u8,
+            Param::ZoneOctave(z) => self.zones[z].octave,
+            // 16 means the zone follows the global channel
+            Param::ZoneChannel(z) => self.zones[z].channel.unwrap_or(16),
+            Param::SeqNote(i) => self.sequencer.steps[i].degree.map_or(0, |d| d + 1),
+            Param::SeqLevels(i) => {
+                let step = self.sequencer.steps[i];
+                let velocity = (step.velocity + 1) / 32;
+                (velocity - 1) | (step.gate - 1) << 2 | (step.probability - 1) << 4
+            }
+        }
+    }
+
+    /// Sets `param` to `value`, clamping it to the valid range
+    pub fn set(&mut self, param: Param, value: u8) {
+        match param {
+            Param::Root => self.root = Note::from(value % 12),
+            Param::Scale => self.scale = Scale::from(value % 7),
+            Param::Velocity => self.velocity = value.clamp(5, 126),
+            Param::Brightness => self.brightness = value.max(5),
+            Param::Channel => self.channel = value.min(15),
+            Param::ZoneCount => {
+                self.zone_count = (value as usize).clamp(1, MAX_ZONES);
+                self.zone = self.zone.min(self.zone_count - 1);
+            }
+            Param::Bpm => self.clock.bpm = (MIN_BPM + value as u16).min(MAX_BPM),
+            Param::ClockOutput => self.clock.output = value != 0,
+            Param::ClockSync => self.clock.sync = value != 0,
+            Param::ArpEnabled => self.arp.enabled = value != 0,
+            Param::ArpLatch => self.arp.latch = value != 0,
+            Param::ArpMode => self.arp.mode = ArpMode::from(value % 6),
+            Param::ArpRate => self.arp.rate = value.min(ARP_RATES.len() as u8 - 1),
+            Param::ArpOctaves => self.arp.octaves = value.clamp(1, 4),
+            Param::ArpGate => self.arp.gate = value.clamp(1, 4),
+            Param::RepeatEnabled => self.repeat.enabled = value != 0,
+            Param::RepeatRate => self.repeat.rate = value.min(ARP_RATES.len() as u8 - 1),
+            Param::HarmonyAbove => self.harmony.above = value & 0xf,
+            Param::HarmonyBelow => self.harmony.below = value & 0xf,
+            Param::SustainCc => self.sustain_cc = value != 0,
+            Param::MonoEnabled => self.mono.enabled = value != 0,
+            Param::MonoPriority => self.mono.priority = Priority::from(value % 3),
+            Param::MonoLegato => self.mono.legato = value != 0,
+            Param::TiltEnabled => self.tilt.enabled = value != 0,
+            Param::TiltSmoothing => self.tilt.motion.smoothing = value.min(7),
+            Param::TiltDeadzone => {
+                self.tilt.motion.deadzone = (value as i16).min(MAX_TILT_DEADZONE)
+            }
+            Param::StrikeEnabled => self.strike.enabled = value != 0,
+            Param::VelocitySource => self.dynamics.source = VelocitySource::from(value % 4),
+            Param::VelocityCurve => self.dynamics.curve = Curve::from(value % 3),
+            Param::VelocitySpread => self.dynamics.spread = value.min(MAX_SPREAD),
+            Param::HostChannel => self.host.channel = value.min(15),
+            Param::Latch => self.latch = value != 0,
+            Param::SeqLength => {
+                self.sequencer.length = match value {
+                    0..=16 => 16,
+                    17..=32 => 32,
+                    _ => 64,
+                };
+                let pages = self.sequencer.length / PAGE_STEPS as u8;
+                self.sequencer.page = self.sequencer.page.min(pages - 1);
+            }
+            Param::TiltRange => {
+                self.tilt.motion.range = (value as i16).clamp(MIN_TILT_RANGE, MAX_TILT_RANGE)
+            }
+            Param::DrumNote(l) => self.drums.lanes[l].note = value.min(126),
+            Param::DrumChannel(l) => self.drums.lanes[l].channel = value.min(15),
+            Param::DrumSteps(i) => self.drums.patterns[i / LANES][i % LANES] = value,
+            // steps are stored first, so pulses and rotation can be clamped to them
+            Param::EuclidSteps(r) => {
+                self.euclid.rhythms[r].steps = value.clamp(1, MAX_EUCLID_STEPS)
+            }
+            Param::EuclidPulses(r) => {
+                self.euclid.rhythms[r].pulses = value.min(self.euclid.rhythms[r].steps)
+            }
+            Param::EuclidRotation(r) => {
+                self.euclid.rhythms[r].rotation = value.min(self.euclid.rhythms[r].steps - 1)
+            }
+            Param::EuclidDegree(r) => self.euclid.rhythms[r].degree = value.min(MAX_EUCLID_DEGREE),
+            Param::FaderCc(f) => self.faders[f].cc = value.min(127),
+            Param::FaderChannel(f) => self.faders[f].channel = (value < 16).then_some(value),
+            Param::TiltTarget(a) => self.tilt.axes[a].target = TiltTarget::from(value % 3),
+            Param::TiltCc(a) => self.tilt.axes[a].cc = value.min(127),
+            Param::TiltCenter(a) => {
+                let mut center = self.tilt.motion.center();
+                center[a] = value as i8 as i16;
+                self.tilt.motion.set_center(center);
+            }
+            Param::StrikeGain(p) => self.strike.gains[p / 7][p % 7] = value.max(1),
+            Param::ZoneStart(z) => self.zones[z].start = value.clamp(1, 7),
+            Param::ZoneKeyboard(z) => self.zones[z].keyboard = Keyboard::from(value % 5),
+            Param::ZoneOctave(z) => self.zones[z].octave = value.min(MAX_OCTAVE),
+            Param::ZoneChannel(z) => self.zones[z].channel = (value < 16).then_some(value),
+            Param::SeqNote(i) => {
+                self.sequencer.steps[i].degree = value.checked_sub(1).map(|d| d.min(MAX_DEGREE))
+            }
+            Param::SeqLevels(i) => {
+                let step = &mut self.sequencer.steps[i];
+                step.velocity = ((value & 3) + 1) * 32 - 1;
+                step.gate = (value >> 2 & 3) + 1;
+                step.probability = (value >> 4 & 3) + 1;
+            }
+        }
+    }
+
+    /// Writes every setting into `buf`, returning the number of bytes used.
+    /// After the magic and the version comes the count, then each id and its value, ids and count in big endian
+    pub fn write_settings(&self, buf: &mut [u8; SETTINGS_LEN]) -> usize {
+        buf[0] = MAGIC;
+        buf[1] = VERSION;
+        let mut len = SETTINGS_HEADER_LEN;
+        for param in Param::all() {
+            buf[len..len + 2].copy_from_slice(&param.id().to_be_bytes());
+            buf[len + 2] = self.get(param);
+            len += SETTING_LEN;
+        }
+        let count = ((len - SETTINGS_HEADER_LEN) / SETTING_LEN) as u16;
+        buf[2..4].copy_from_slice(&count.to_be_bytes());
+        len
+    }
+
+    /// Reads settings written by `write_settings`, ignoring any unknown ids.
+    /// Returns false if `buf` doesn't hold settings
+    pub fn read_settings(&mut self, buf: &[u8]) -> bool {
+        let Some(len) = settings_len(buf) else {
+            return false;
+        };
+        for setting in buf[SETTINGS_HEADER_LEN..len.min(buf.len())].chunks_exact(SETTING_LEN) {
+            if let Some(param) = Param::from_id(u16::from_be_bytes([setting[0], setting[1]])) {
+                self.set(param, setting[2]);
+            }
+        }
+        self.check_zones();
+        true
+    }
+
+    /// Zones must be sorted and can't be empty, otherwise the splits are dropped
+    pub fn check_zones(&mut self) {
+        let sorted = (1..self.zone_count).all(|z| self.zones[z - 1].start < self.zones[z].start);
+        if !sorted || self.zones[0].start != 1 {
+            self.zones[0].start = 1;
+            self.zone_count = 1;
+            self.zone = 0;
+        }
+    }
+
+    /// Loads the settings stored in flash
+    pub fn load_settings(&mut self) {
+        let mut buf = [0; SETTINGS_LEN];
+        self.board.read_settings(0, &mut buf);
+        self.read_settings(&buf);
+    }
+
+    /// Stores the settings in flash, if they changed
+    pub fn save_settings(&mut self) {
+        self.store_settings(0);
+    }
+
+    /// Stores the current settings as preset `p`
+    pub fn save_preset(&mut self, p: usize) {
+        self.store_settings(p + 1);
+    }
+
+    /// Loads preset `p` and makes it the current settings.
+    /// Returns false if nothing was saved there
+    pub fn load_preset(&mut self, p: usize) -> bool {
+        let mut buf = [0; SETTINGS_LEN];
+        self.board.read_settings(p + 1, &mut buf);
+        if !self.read_settings(&buf) {
+            return false;
+        }
+        self.save_settings();
+        true
+    }
+
+    /// Reads preset `p` into `buf` without loading it, returning its length.
+    /// Returns `None` if nothing was saved there
+    pub fn dump_preset(&self, p: usize, buf: &mut [u8; SETTINGS_LEN]) -> Option<usize> {
+        self.board.read_settings(p + 1, buf);
+        settings_len(buf).filter(|&len| len <= SETTINGS_LEN)
+    }
+
+    /// Stores a dump as preset `p` as it is, leaving the current settings alone.
+    /// Returns false if `dump` doesn't hold settings
+    pub fn restore_preset(&mut self, p: usize, dump: &[u8]) -> bool {
+        if settings_len(dump).is_none() || dump.len() > SETTINGS_LEN {
+            return false;
+        }
+        let mut buf = [0; SETTINGS_LEN];
+        buf[..dump.len()].copy_from_slice(dump);
+        self.store(p + 1, &buf, dump.len());
+        true
+    }
+
+    fn store_settings(&mut self, slot: usize) {
+        let mut buf = [0; SETTINGS_LEN];
+        let len = self.write_settings(&mut buf);
+        self.store(slot, &buf, len);
+    }
+
+    /// Writes `buf` to `slot`, unless its first `len` bytes are already there
+    fn store(&mut self, slot: usize, buf: &[u8; SETTINGS_LEN], len: usize) {
+        let mut stored = [0; SETTINGS_LEN];
+        self.board.read_settings(slot, &mut stored);
+        if stored[..len] != buf[..len] {
+            self.board.write_settings(slot, buf);
+            self.log(format_args!("stored settings in slot {slot}"));
+        }
+    }
+}
@@ -5,61 +5,177 @@ use bsp::hal::ehal::digital::v2::InputPin;
 use usbd_midi::midi_types;
 
 use crate::{
+    arp::Arp,
     board::Board,
+    clock::Clock,
+    console::Console,
+    drums::Drums,
+    dynamics::Dynamics,
+    euclid::Euclid,
+    faders::{Fader, DEFAULT_FADERS, FADERS},
+    harmony::Harmony,
+    host::Host,
     keys::*,
-    music_theory::{Note, Scale},
-    usb::send_midi,
+    looper::{LoopState, Looper},
+    mono::Mono,
+    music_theory::{Chord, Note, Scale},
+    repeat::Repeat,
+    sequencer::Sequencer,
+    strike::Strike,
+    sysex::SysexBuffer,
+    tilt::Tilt,
+    usb::{send_cc, send_midi},
 };
 
 pub const MAX_OCTAVE: u8 = 8;
+pub const MAX_ZONES: usize = 4;
 
 pub struct State {
     pub board: Board,
 
     pub keys: [KeyState; bsp::NEOPIXEL_COUNT],
+    /// when each key was last pressed, in microseconds
+    pressed_at: [u64; bsp::NEOPIXEL_COUNT],
 
     pub mode: Mode,
-    pub keyboard: Keyboard,
+    /// mode to go back to after leaving config or root select
+    pub home: Mode,
+
+    /// zones the playable columns are split into, sorted by starting column.
+    /// only the first `zone_count` are in use
+    pub zones: [Zone; MAX_ZONES],
+    pub zone_count: usize,
+    /// zone edited by config mode
+    pub zone: usize,
 
     pub brightness: u8,
 
     pub scale: Scale,
     pub root: Note,
-    pub octave: u8,
     pub velocity: u8,
+    /// velocity of the pad or recorded note being played, overriding `velocity` while its notes are sent
+    pad_velocity: Option<u8>,
+    /// velocity each pad is playing its notes with, `None` while they're off
+    pad_velocities: [Option<u8>; 32],
+    pub dynamics: Dynamics,
+    /// channel used by zones that don't set their own
+    pub channel: u8,
 
     pub sustain: bool,
-
-    active_notes: [bool; 127],
-    pub sustained_notes: [bool; 127],
+    /// whether the fourth key did something else while held, so letting go of it doesn't toggle sustain
+    pub sustain_key_used: bool,
+    /// whether sustain sends CC64 to let the synth hold notes, instead of holding them here
+    pub sustain_cc: bool,
+    /// with latch on, each pad toggles its notes instead of playing them while held
+    pub latch: bool,
+    /// pads whose notes are latched on, as a bitmask of key indices
+    latched: u32,
+
+    pub clock: Clock,
+    pub arp: Arp,
+    pub harmony: Harmony,
+    pub mono: Mono,
+    pub sequencer: Sequencer,
+    pub drums: Drums,
+    pub euclid: Euclid,
+    pub faders: [Fader; FADERS],
+    pub looper: Looper,
+    pub repeat: Repeat,
+    pub tilt: Tilt,
+    pub strike: Strike,
+    pub host: Host,
+    /// sysex message being received
+    pub sysex: SysexBuffer,
+    pub console: Console,
+
+    rng: u32,
+
+    active_notes: [[bool; 127]; 16],
+    pub sustained_notes: [[bool; 127]; 16],
+
+    pub column: Column,
+    /// when every key of the panic chord was first held down
+    pub panic_at: Option<u64>,
+    /// whether the panic gesture was held long enough to sweep every note
+    pub panic_long_press: bool,
+    /// whether the fourth key already started or stopped the transport, or was used as a modifier, since it was pressed
+    pub transport_long_press: bool,
 }
 impl State {
     pub fn new() -> Self {
-        Self {
+        let mut state = Self {
             board: Board::new(),
 
             keys: [KeyState::Unpressed; bsp::NEOPIXEL_COUNT],
+            pressed_at: [0; bsp::NEOPIXEL_COUNT],
 
             mode: Mode::Normal,
-            keyboard: Keyboard::Scale,
+            home: Mode::Normal,
+
+            zones: [Zone {
+                start: 1,
+                keyboard: Keyboard::Scale,
+                octave: 3,
+                channel: None,
+            }; MAX_ZONES],
+            zone_count: 1,
+            zone: 0,
 
             brightness: 30,
 
             scale: Scale::Ionian,
             root: Note::C,
-            octave: 3,
             velocity: 70,
+            pad_velocity: None,
+            pad_velocities: [None; 32],
+            dynamics: Dynamics::new(),
+            channel: 1,
 
             sustain: false,
-
-            active_notes: [false; 127],
-            sustained_notes: [false; 127],
-        }
+            sustain_key_used: false,
+            sustain_cc: false,
+            latch: false,
+            latched: 0,
+
+            clock: Clock::new(),
+            arp: Arp::new(),
+            harmony: Harmony::new(),
+            mono: Mono::new(),
+            sequencer: Sequencer::new(),
+            drums: Drums::new(),
+            euclid: Euclid::new(),
+            faders: DEFAULT_FADERS,
+            looper: Looper::new(),
+            repeat: Repeat::new(),
+            tilt: Tilt::new(),
+            strike: Strike::new(),
+            host: Host::new(),
+            sysex: SysexBuffer::new(),
+            console: Console::new(),
+
+            rng: 0x2545_f491,
+
+            active_notes: [[false; 127]; 16],
+            sustained_notes: [[false; 127]; 16],
+
+            column: Column::new(),
+            panic_at: None,
+            panic_long_press: false,
+            transport_long_press: false,
+        };
+        state.load_settings();
+        state
     }
 
     /// Updates the KeyState of every key
     pub fn update_keys(&mut self) {
         let keypad_inputs = self.board.keypad.decompose();
+        let now = self.board.micros();
+
+        let held = (0..4)
+            .filter(|&row| !keypad_inputs[row][0].is_high().unwrap())
+            .fold(0, |held, row| held | 1 << row);
+        self.column.update(held, now);
 
         for i in 0..bsp::NEOPIXEL_COUNT {
             let keypad_column = i % 8;
@@ -67,7 +183,11 @@ impl State {
             let keypad_button: &dyn InputPin<Error = ()> =
                 &keypad_inputs[keypad_row][keypad_column];
 
-            let pressed = !keypad_button.is_high().unwrap();
+            let pressed = if keypad_column == 0 {
+                self.column.passed & (1 << keypad_row) != 0
+            } else {
+                !keypad_button.is_high().unwrap()
+            };
             self.keys[i] = match self.keys[i] {
                 KeyState::Unpressed | KeyState::JustReleased if pressed => KeyState::JustPressed,
                 KeyState::JustPressed if pressed => KeyState::Pressed,
@@ -75,69 +195,341 @@ impl State {
                 KeyState::JustReleased if !pressed => KeyState::Unpressed,
                 keep => keep,
             };
+            if self.keys[i] == KeyState::JustPressed {
+                self.pressed_at[i] = now;
+            }
         }
     }
 
+    /// Reads the accelerometer once for everything that uses it
+    pub fn update_accel(&mut self) {
+        if !self.tilt.enabled && !self.strike.enabled {
+            return;
+        }
+        let Some(sample) = self.board.read_accel() else {
+            return;
+        };
+        self.update_tilt(sample);
+        self.strike.shock.update(sample);
+    }
+
     pub fn update_sustain(&mut self) {
+        // the fourth key undoes the looper's last overdub while holding the looper key
         if self.key_just_pressed((0, 3)) {
-            self.sustain = !self.sustain;
-            for i in 0..127u8 {
-                if self.sustained_notes[i as usize] {
-                    self.send_midi(i, false);
+            self.sustain_key_used = self.key_pressed((0, 2));
+        }
+        // while note repeat is on, holding it picks the rate, so sustain waits until it's let go
+        let toggle = if self.repeat.enabled {
+            self.key_just_released((0, 3))
+        } else {
+            self.key_just_pressed((0, 3))
+        };
+        if toggle && !self.sustain_key_used {
+            self.set_sustain(!self.sustain);
+        }
+    }
+
+    /// Presses or lets go of the sustain pedal
+    pub fn set_sustain(&mut self, sustain: bool) {
+        self.sustain = sustain;
+        if self.sustain_cc {
+            for z in 0..self.zone_count {
+                send_cc(self.zone_channel(z), 64, if sustain { 127 } else { 0 });
+            }
+            return;
+        }
+
+        // like a real pedal, only the notes let go of while it was down stop
+        if !sustain {
+            for channel in 0..16u8 {
+                for i in 0..127u8 {
+                    if self.sustained_notes[channel as usize][i as usize] {
+                        self.sustained_notes[channel as usize][i as usize] = false;
+                        self.send_midi(channel, i, false);
+                    }
                 }
             }
         }
     }
 
     pub fn set_mode(&mut self, mode: Mode) {
+        if let Mode::Config
+        | Mode::Zones
+        | Mode::Arp
+        | Mode::Harmony
+        | Mode::Tilt
+        | Mode::Strike
+        | Mode::Velocity
+        | Mode::Tempo
+        | Mode::Sequencer
+        | Mode::Drums
+        | Mode::Euclid
+        | Mode::Faders
+        | Mode::Host = self.mode
+        {
+            self.save_settings();
+        }
         self.mode = mode;
+        // every note is turned off below, so latched pads are released too
+        self.latched = 0;
+        self.pad_velocities = [None; 32];
 
-        for i in 0..127u8 {
-            if self.active_notes[i as usize] {
-                self.send_midi(i, false);
+        if !self.arp.latch {
+            self.arp.clear();
+        }
+        self.mono.clear();
+
+        for channel in 0..16u8 {
+            for i in 0..127u8 {
+                if self.active_notes[channel as usize][i as usize] {
+                    self.send_midi(channel, i, false);
+                }
             }
         }
     }
 
-    pub fn send_midi(&mut self, midi_num: u8, on: bool) {
-        let midi_num = midi_num.min(126);
+    /// Plays the notes of the pad at `(col, row)`, including the ones added by the harmonizer
+    pub fn play_pad(&mut self, col: u8, row: u8, on: bool) {
+        let velocity = on.then(|| self.velocity_for(col, row));
+        self.pad_velocities[(col, row).into_index()] = velocity;
+        self.pad_velocity = velocity;
+        self.play_pad_notes(col, row, on);
+    }
 
-        // dont do anything if the note is already active
-        if self.active_notes[midi_num as usize] && on {
-            return;
+    /// Plays the notes of a pad that's playing again, at the velocity it was pressed with, like note repeat does.
+    /// Returns false if the pad isn't playing
+    pub fn replay_pad(&mut self, col: u8, row: u8, on: bool) -> bool {
+        let Some(velocity) = self.pad_velocities[(col, row).into_index()] else {
+            return false;
+        };
+        self.pad_velocity = Some(velocity);
+        self.play_pad_notes(col, row, on);
+        true
+    }
+
+    fn play_pad_notes(&mut self, col: u8, row: u8, on: bool) {
+        let (channel, note, intervals) = self.pad_notes(col, row);
+        for i in intervals {
+            self.play(channel, note + i, on);
+        }
+        let (harmony, len) = self.harmony_notes(col, note);
+        for &n in &harmony[..len] {
+            self.play(channel, n, on);
         }
+        self.pad_velocity = None;
+    }
 
-        // TODO make sustain pedal only sustian the chords that are played while it's held
-        // this way we can sustain some notes and not others
+    /// Toggles whether the pad `i` is latched, returning whether it's latched now
+    pub fn toggle_latched(&mut self, i: impl KeyIndex) -> bool {
+        let i = i.into_index();
+        self.latched ^= 1 << i;
+        self.latched & (1 << i) != 0
+    }
 
-        if on {
-            self.active_notes[midi_num as usize] = true;
-            if self.sustain {
-                self.sustained_notes[midi_num as usize] = true;
+    pub fn latched(&self, i: impl KeyIndex) -> bool {
+        self.latched & (1 << i.into_index()) != 0
+    }
+
+    /// Plays a note from the pads, recording it if the looper is recording
+    pub fn play(&mut self, channel: u8, note: u8, on: bool) {
+        self.looper.record(channel, note, self.note_velocity(), on);
+        self.route_note(channel, note, on);
+    }
+
+    /// Like `route_note`, but for a note played before at `velocity`, like a recorded one
+    pub fn replay_note(&mut self, channel: u8, note: u8, velocity: u8, on: bool) {
+        let pad_velocity = self.pad_velocity.replace(velocity);
+        self.route_note(channel, note, on);
+        self.pad_velocity = pad_velocity;
+    }
+
+    /// Plays a note going through the arpeggiator or mono mode if they're enabled
+    pub fn route_note(&mut self, channel: u8, note: u8, on: bool) {
+        if self.arp.enabled {
+            self.arp.note(channel, note, self.note_velocity(), on);
+        } else if self.mono.enabled {
+            self.mono_note(channel, note, on);
+        } else {
+            self.send_midi(channel, note, on);
+        }
+    }
+
+    pub fn send_midi(&mut self, channel: u8, midi_num: u8, on: bool) {
+        self.send_note(channel, midi_num, self.note_velocity(), on);
+    }
+
+    /// Returns the velocity notes are sent with right now, the pad's own or the global one
+    pub fn note_velocity(&self) -> u8 {
+        self.pad_velocity.unwrap_or(self.velocity)
+    }
+
+    /// Like `send_midi`, but with a velocity other than the global one
+    pub fn send_note(&mut self, channel: u8, midi_num: u8, velocity: u8, on: bool) {
+        let midi_num = midi_num.min(126);
+        let c = channel as usize;
+        let n = midi_num as usize;
+
+        // dont do anything if the note is already active,
+        // but a note held by the pedal is struck again, so the synth plays it again
+        if self.active_notes[c][n] && on {
+            if !self.sustained_notes[c][n] {
+                return;
             }
+            self.sustained_notes[c][n] = false;
 
             let note = midi_types::Note::new(midi_num);
-            send_midi(note, self.velocity, true);
-        } else if !self.sustain {
-            self.active_notes[midi_num as usize] = false;
-            self.sustained_notes[midi_num as usize] = false;
+            send_midi(channel, note, 0, false);
+            self.board.delay.delay_us(150u8);
+            send_midi(channel, note, velocity, true);
+            self.board.delay.delay_us(150u8);
+            return;
+        }
+
+        if on {
+            self.active_notes[c][n] = true;
 
             let note = midi_types::Note::new(midi_num);
-            send_midi(note, 0, false);
+            send_midi(channel, note, velocity, true);
+        } else if self.sustain && !self.sustain_cc {
+            // the pedal holds the note until it's let go of
+            if self.active_notes[c][n] {
+                self.sustained_notes[c][n] = true;
+            }
         } else {
-            self.sustained_notes[midi_num as usize] = true;
+            self.active_notes[c][n] = false;
+            self.sustained_notes[c][n] = false;
+
+            let note = midi_types::Note::new(midi_num);
+            send_midi(channel, note, 0, false);
         }
 
         self.board.delay.delay_us(150u8)
     }
 
-    pub fn note_off_all(&mut self) {
+    /// Stops every sound: sends all notes off and all sound off on every channel in use,
+    /// forgets every held, sustained and latched note, and stops the transport and the looper
+    /// so the sequencer, drums and euclid rhythms stop too.
+    /// `sweep` also sends note off for every note, for synths that ignore those messages
+    pub fn panic(&mut self, sweep: bool) {
+        self.log(format_args!("panic"));
         self.sustain = false;
-        for i in 0..127u8 {
-            self.send_midi(i, false);
+        self.latched = 0;
+        self.pad_velocities = [None; 32];
+        self.arp.clear();
+        self.mono.clear();
+        if self.clock.running {
+            self.stop();
+        }
+        match self.looper.state {
+            LoopState::Playing | LoopState::Overdubbing => self.looper.state = LoopState::Stopped,
+            // a loop that isn't closed yet can't be kept
+            LoopState::Armed | LoopState::Recording => self.looper.clear(),
+            _ => {}
+        }
+        self.active_notes = [[false; 127]; 16];
+        self.sustained_notes = [[false; 127]; 16];
+        self.host.incoming = [0; 16];
+
+        let channels = self.channels_in_use();
+        for channel in 0..16u8 {
+            if channels & (1 << channel) == 0 {
+                continue;
+            }
+            send_cc(channel, 64, 0);
+            send_cc(channel, 123, 0);
+            send_cc(channel, 120, 0);
+            self.board.delay.delay_us(150u8);
+
+            if sweep {
+                for i in 0..128u8 {
+                    send_midi(channel, midi_types::Note::new(i), 0, false);
+                    self.board.delay.delay_us(150u8);
+                }
+            }
+        }
+    }
+
+    /// Returns how many notes are on right now, on every channel
+    pub fn notes_playing(&self) -> usize {
+        self.active_notes.iter().flatten().filter(|&&on| on).count()
+    }
+
+    /// Returns every channel notes can be played on, as a bitmask
+    pub fn channels_in_use(&self) -> u16 {
+        let mut channels = 1 << self.channel;
+        for z in 0..self.zone_count {
+            channels |= 1 << self.zone_channel(z);
+        }
+        for lane in &self.drums.lanes {
+            channels |= 1 << lane.channel;
+        }
+        channels
+    }
+
+    /// Returns the channel zone `z` plays on
+    pub fn zone_channel(&self, z: usize) -> u8 {
+        self.zones[z].channel.unwrap_or(self.channel)
+    }
+
+    /// Returns the index of the zone that contains column `col`
+    pub fn zone_at(&self, col: u8) -> usize {
+        (0..self.zone_count)
+            .rev()
+            .find(|&z| self.zones[z].start <= col)
+            .unwrap_or(0)
+    }
+
+    /// Returns the last column of zone `z`
+    pub fn zone_end(&self, z: usize) -> u8 {
+        if z + 1 < self.zone_count {
+            self.zones[z + 1].start - 1
+        } else {
+            7
         }
     }
 
+    /// Splits the zone containing `col` so a new zone starts at `col`,
+    /// or merges it back into the previous one if a zone already starts there
+    pub fn toggle_split(&mut self, col: u8) {
+        let z = self.zone_at(col);
+        if self.zones[z].start == col {
+            if z == 0 {
+                return;
+            }
+            self.zones.copy_within(z + 1..self.zone_count, z);
+            self.zone_count -= 1;
+        } else if self.zone_count < MAX_ZONES {
+            self.zones.copy_within(z..self.zone_count, z + 1);
+            self.zones[z + 1].start = col;
+            self.zone_count += 1;
+        }
+        self.zone = self.zone.min(self.zone_count - 1);
+    }
+
+    /// Returns the channel, root note and intervals played by the pad at `(col, row)`
+    pub fn pad_notes(&self, col: u8, row: u8) -> (u8, u8, &'static [u8]) {
+        let z = self.zone_at(col);
+        let zone = self.zones[z];
+        let width = self.zone_end(z) + 1 - zone.start;
+        let (note, intervals) = zone.keyboard.pad(
+            col - zone.start,
+            row,
+            width,
+            zone.octave * 12 + self.root as u8,
+            self.scale,
+        );
+        (self.zone_channel(z), note, intervals)
+    }
+
+    /// Returns a pseudo random number
+    pub fn random(&mut self) -> u32 {
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+
     pub fn key_pressed(&self, i: impl KeyIndex) -> bool {
         self.keys[i.into_index()].pressed()
     }
@@ -149,16 +541,53 @@ impl State {
     pub fn key_just_released(&self, i: impl KeyIndex) -> bool {
         self.keys[i.into_index()] == KeyState::JustReleased
     }
+
+    /// Returns how long a key has been held for, in microseconds
+    pub fn key_held_for(&mut self, i: impl KeyIndex) -> u64 {
+        let i = i.into_index();
+        if !self.keys[i].pressed() {
+            return 0;
+        }
+        self.board.micros() - self.pressed_at[i]
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Mode {
     Normal,
-    SelectRoot { hold: bool },
+    SelectRoot {
+        hold: bool,
+    },
     Config,
+    Zones,
+    Arp,
+    Harmony,
+    Tilt,
+    /// calibrates strike velocity, one pad at a time
+    Strike,
+    Velocity,
+    Tempo,
+    Sequencer,
+    /// sequencer settings, shown while holding the third control pad
+    SequencerMenu,
+    Drums,
+    Euclid,
+    Faders,
+    /// pads are lit and read by the host
+    Host,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone)]
+pub struct Zone {
+    /// first column of the zone, from 1 to 7
+    pub start: u8,
+    pub keyboard: Keyboard,
+    pub octave: u8,
+    /// `None` follows the global channel
+    pub channel: Option<u8>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Keyboard {
     Scale,
     Chords,
@@ -166,3 +595,37 @@ pub enum Keyboard {
     Bass,
     Waffletone,
 }
+
+impl Keyboard {
+    pub const fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Scale,
+            1 => Self::Chords,
+            2 => Self::Sampler,
+            3 => Self::Bass,
+            4 => Self::Waffletone,
+            _ => panic!("number is not in 0..5"),
+        }
+    }
+
+    /// Returns the note and the intervals on top of it played by the pad at `(col, row)`,
+    /// where `col` is relative to the start of a zone `width` columns wide
+    /// and `base` is the root note of the zone's octave
+    pub fn pad(self, col: u8, row: u8, width: u8, base: u8, scale: Scale) -> (u8, &'static [u8]) {
+        match self {
+            Keyboard::Scale => (base + row * 12 + scale.get(col % 7), &[0]),
+            Keyboard::Chords => {
+                let chord = match row {
+                    0 => Chord::Major,
+                    1 => Chord::Minor,
+                    2 => Chord::Diminished,
+                    _ => Chord::Power,
+                };
+                (base + scale.get(col % 7), chord.notes())
+            }
+            Keyboard::Sampler => (base + (3 - row) + (width - 1 - col) * 4, &[0]),
+            Keyboard::Bass => (base + (3 - row) * 5 + col, &[0]),
+            Keyboard::Waffletone => ((base + col * 3).saturating_sub(row), &[0]),
+        }
+    }
+}
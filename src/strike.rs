@@ -0,0 +1,43 @@
+use motion::{strike_gain, strike_velocity, UNITY_GAIN};
+
+use crate::state::State;
+
+/// Estimates how hard pads are hit from the shock the accelerometer picks up
+pub struct Strike {
+    pub enabled: bool,
+    /// gain of every playable pad, by row and column, since pads further from the sensor shake it less
+    pub gains: [[u8; 7]; 4],
+    pub shock: motion::Strike,
+}
+
+impl Strike {
+    pub const fn new() -> Self {
+        Self {
+            enabled: false,
+            gains: [[UNITY_GAIN; 7]; 4],
+            shock: motion::Strike::new(),
+        }
+    }
+}
+
+impl State {
+    /// Returns the velocity for the pad at `(col, row)` being hit just now,
+    /// or the fixed velocity if strike velocity is off or the hit was too soft to measure
+    pub fn strike_velocity(&self, col: u8, row: u8) -> u8 {
+        if !self.strike.enabled {
+            return self.velocity;
+        }
+        let gain = self.strike.gains[row as usize][col as usize - 1];
+        strike_velocity(self.strike.shock.peak(), gain).unwrap_or(self.velocity)
+    }
+
+    /// Sets the gain of the pad at `(col, row)` so the hit it just got plays at a medium velocity.
+    /// Returns whether the hit was strong enough to calibrate with
+    pub fn calibrate_strike(&mut self, col: u8, row: u8) -> bool {
+        let Some(gain) = strike_gain(self.strike.shock.peak()) else {
+            return false;
+        };
+        self.strike.gains[row as usize][col as usize - 1] = gain;
+        true
+    }
+}
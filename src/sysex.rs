@@ -0,0 +1,365 @@
+use crate::{
+    settings::{settings_len, Param, PRESETS, SETTINGS_LEN},
+    state::State,
+};
+
+/// the non-commercial manufacturer id, followed by a byte for the matriu
+pub const MANUFACTURER_ID: u8 = 0x7d;
+pub const DEVICE_ID: u8 = 0x4d;
+/// bumped whenever the meaning of a command changes
+pub const PROTOCOL_VERSION: u8 = 2;
+/// family and model sent in the identity reply, least significant byte first
+const FAMILY: [u8; 2] = [DEVICE_ID, 0x00];
+const MODEL: [u8; 2] = [0x01, 0x00];
+
+/// bytes before the data of a message: `f0`, the ids, the protocol version and the command
+const HEADER_LEN: usize = 5;
+/// most data a message carries, a preset number followed by a full dump of the settings
+const DATA_LEN: usize = SETTINGS_LEN + 1;
+/// longest message sent or received
+pub const SYSEX_LEN: usize = HEADER_LEN + DATA_LEN * 2 + 1;
+
+/// firmware version as major, minor and patch
+pub const FIRMWARE_VERSION: [u8; 3] = [
+    parse_version(env!("CARGO_PKG_VERSION_MAJOR")),
+    parse_version(env!("CARGO_PKG_VERSION_MINOR")),
+    parse_version(env!("CARGO_PKG_VERSION_PATCH")),
+];
+
+const fn parse_version(s: &str) -> u8 {
+    let bytes = s.as_bytes();
+    let mut n = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        n = n * 10 + (bytes[i] - b'0');
+        i += 1;
+    }
+    n
+}
+
+/// Requests the host can send. Replies have the same command with `REPLY` set
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// replies with the firmware version and the protocol version
+    Version,
+    /// takes a param id in two bytes, high first, replies with the id and its value
+    Get,
+    /// takes a param id in two bytes and a value, replies like `Get` with the value that was set
+    Set,
+    /// replies with every setting, in the format they're stored in flash
+    Dump,
+    /// takes a dump, loads it and stores it in flash
+    Restore,
+    /// stores the current settings in flash
+    Save,
+    /// takes a preset number, and stores the current settings in it
+    SavePreset,
+    /// takes a preset number, and loads it
+    LoadPreset,
+    /// takes a preset number, replies with what's stored in it like `Dump`, without loading it
+    DumpPreset,
+    /// takes a preset number and a dump, and stores the dump in the preset without loading it
+    RestorePreset,
+}
+
+/// set on the command of every reply
+pub const REPLY: u8 = 0x40;
+/// command of the reply to a request that failed, followed by the request's command and an `Error`
+pub const ERROR: u8 = 0x7f;
+
+impl Command {
+    pub const fn from_id(id: u8) -> Option<Self> {
+        Some(match id {
+            0x01 => Self::Version,
+            0x02 => Self::Get,
+            0x03 => Self::Set,
+            0x04 => Self::Dump,
+            0x05 => Self::Restore,
+            0x06 => Self::Save,
+            0x07 => Self::SavePreset,
+            0x08 => Self::LoadPreset,
+            0x09 => Self::DumpPreset,
+            0x0a => Self::RestorePreset,
+            _ => return None,
+        })
+    }
+
+    pub const fn id(self) -> u8 {
+        match self {
+            Self::Version => 0x01,
+            Self::Get => 0x02,
+            Self::Set => 0x03,
+            Self::Dump => 0x04,
+            Self::Restore => 0x05,
+            Self::Save => 0x06,
+            Self::SavePreset => 0x07,
+            Self::LoadPreset => 0x08,
+            Self::DumpPreset => 0x09,
+            Self::RestorePreset => 0x0a,
+        }
+    }
+}
+
+/// Why a request failed
+#[derive(Copy, Clone)]
+pub enum Error {
+    UnknownCommand = 1,
+    /// the request was made for a different protocol version
+    Version = 2,
+    /// the request has too much or too little data
+    Length = 3,
+    UnknownParam = 4,
+    UnknownPreset = 5,
+    /// the dump or preset doesn't hold settings
+    BadSettings = 6,
+}
+
+/// Puts together sysex messages split over several USB MIDI packets, or sent a byte at a time
+pub struct SysexBuffer {
+    pub buf: [u8; SYSEX_LEN],
+    len: usize,
+    /// whether the message being received didn't fit, so it'll be dropped
+    overflow: bool,
+}
+
+impl SysexBuffer {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; SYSEX_LEN],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Adds the bytes of a packet with a sysex code index number, `0x4` to `0x7`.
+    /// Returns the length of the message in `buf` once it's complete
+    pub fn push(&mut self, packet: [u8; 4]) -> Option<usize> {
+        let count = match packet[0] & 0x0f {
+            0x4 | 0x7 => 3,
+            0x5 => 1,
+            0x6 => 2,
+            _ => return None,
+        };
+
+        packet[1..1 + count]
+            .iter()
+            .find_map(|&byte| self.push_byte(byte))
+    }
+
+    /// Adds a single byte, like the ones received on the serial port.
+    /// Returns the length of the message in `buf` once it's complete
+    pub fn push_byte(&mut self, byte: u8) -> Option<usize> {
+        match byte {
+            0xf0 => {
+                self.len = 0;
+                self.overflow = false;
+            }
+            0xf7 => {}
+            // realtime messages can show up in the middle of sysex
+            0xf8.. => return None,
+            // any other status byte ends the sysex without finishing it
+            0x80.. => {
+                self.len = 0;
+                return None;
+            }
+            _ => {}
+        }
+        if self.len == SYSEX_LEN {
+            self.overflow = true;
+        } else {
+            self.buf[self.len] = byte;
+            self.len += 1;
+        }
+
+        if byte != 0xf7 {
+            return None;
+        }
+        let len = self.len;
+        self.len = 0;
+        if self.overflow || self.buf[0] != 0xf0 {
+            return None;
+        }
+        Some(len)
+    }
+
+    /// Returns whether a message has started and isn't complete yet
+    pub fn receiving(&self) -> bool {
+        self.len > 0
+    }
+}
+
+/// Sends a reply with `send`, splitting every data byte into two nibbles so it fits in 7 bits
+fn reply(send: fn(&[u8]), command: u8, data: &[u8]) {
+    let mut buf = [0; SYSEX_LEN];
+    buf[..HEADER_LEN].copy_from_slice(&[
+        0xf0,
+        MANUFACTURER_ID,
+        DEVICE_ID,
+        PROTOCOL_VERSION,
+        command,
+    ]);
+    let mut len = HEADER_LEN;
+    for &byte in data {
+        buf[len] = byte >> 4;
+        buf[len + 1] = byte & 0x0f;
+        len += 2;
+    }
+    buf[len] = 0xf7;
+    send(&buf[..len + 1]);
+}
+
+fn reply_error(send: fn(&[u8]), command: u8, error: Error) {
+    reply(send, ERROR, &[command, error as u8]);
+}
+
+/// Answers a universal identity request, so editors can tell what's connected
+fn reply_identity(send: fn(&[u8])) {
+    let [major, minor, patch] = FIRMWARE_VERSION;
+    let [family_lsb, family_msb] = FAMILY;
+    let [model_lsb, model_msb] = MODEL;
+    send(&[
+        0xf0,
+        0x7e,
+        // all devices, since the matriu doesn't have a device id
+        0x7f,
+        0x06,
+        0x02,
+        MANUFACTURER_ID,
+        family_lsb,
+        family_msb,
+        model_lsb,
+        model_msb,
+        major,
+        minor,
+        patch,
+        0,
+        0xf7,
+    ]);
+}
+
+/// Checks a dump sent by the host before using it, since one missing bytes
+/// would set params to the values of other ones
+fn check_dump(dump: &[u8]) -> Result<(), Error> {
+    match settings_len(dump) {
+        None => Err(Error::BadSettings),
+        Some(len) if len != dump.len() => Err(Error::Length),
+        Some(_) => Ok(()),
+    }
+}
+
+impl State {
+    /// Handles a complete sysex message, ignoring the ones that aren't for us.
+    /// replies go back with `send`, the same way the message came in
+    pub fn handle_sysex(&mut self, message: &[u8], send: fn(&[u8])) {
+        // universal non-realtime identity request, for any device id
+        if let [0xf0, 0x7e, _, 0x06, 0x01, 0xf7] = *message {
+            return reply_identity(send);
+        }
+
+        let [0xf0, MANUFACTURER_ID, DEVICE_ID, version, command, ref data @ .., 0xf7] = *message
+        else {
+            return;
+        };
+        self.log(format_args!("sysex command {command:#04x}"));
+        if version != PROTOCOL_VERSION {
+            return reply_error(send, command, Error::Version);
+        }
+        let Some(cmd) = Command::from_id(command) else {
+            return reply_error(send, command, Error::UnknownCommand);
+        };
+
+        // data bytes come in as two nibbles each, high first
+        if data.len() % 2 != 0 || data.len() > DATA_LEN * 2 {
+            return reply_error(send, command, Error::Length);
+        }
+        let mut buf = [0; DATA_LEN];
+        let len = data.len() / 2;
+        for (b, nibbles) in buf.iter_mut().zip(data.chunks_exact(2)) {
+            *b = (nibbles[0] & 0x0f) << 4 | (nibbles[1] & 0x0f);
+        }
+        let data = &buf[..len];
+
+        let expected = match cmd {
+            Command::SavePreset | Command::LoadPreset | Command::DumpPreset => 1,
+            Command::Get => 2,
+            Command::Set => 3,
+            Command::Restore => len,
+            Command::RestorePreset => len.max(1),
+            _ => 0,
+        };
+        if len != expected {
+            return reply_error(send, command, Error::Length);
+        }
+
+        let command = command | REPLY;
+        match cmd {
+            Command::Version => {
+                let [major, minor, patch] = FIRMWARE_VERSION;
+                reply(send, command, &[major, minor, patch, PROTOCOL_VERSION]);
+            }
+            Command::Get | Command::Set => {
+                let Some(param) = Param::from_id(u16::from_be_bytes([data[0], data[1]])) else {
+                    return reply_error(send, cmd.id(), Error::UnknownParam);
+                };
+                if cmd == Command::Set {
+                    self.set(param, data[2]);
+                    self.check_zones();
+                }
+                reply(send, command, &[data[0], data[1], self.get(param)]);
+            }
+            Command::Dump => {
+                let mut settings = [0; SETTINGS_LEN];
+                let len = self.write_settings(&mut settings);
+                reply(send, command, &settings[..len]);
+            }
+            Command::Restore => {
+                if let Err(e) = check_dump(data) {
+                    return reply_error(send, cmd.id(), e);
+                }
+                self.read_settings(data);
+                self.save_settings();
+                reply(send, command, &[]);
+            }
+            Command::Save => {
+                self.save_settings();
+                reply(send, command, &[]);
+            }
+            Command::SavePreset | Command::LoadPreset => {
+                let p = data[0] as usize;
+                if p >= PRESETS {
+                    return reply_error(send, cmd.id(), Error::UnknownPreset);
+                }
+                if cmd == Command::SavePreset {
+                    self.save_preset(p);
+                } else if !self.load_preset(p) {
+                    return reply_error(send, cmd.id(), Error::BadSettings);
+                }
+                reply(send, command, &[]);
+            }
+            Command::DumpPreset => {
+                let p = data[0] as usize;
+                if p >= PRESETS {
+                    return reply_error(send, cmd.id(), Error::UnknownPreset);
+                }
+                let mut settings = [0; SETTINGS_LEN];
+                let Some(len) = self.dump_preset(p, &mut settings) else {
+                    return reply_error(send, cmd.id(), Error::BadSettings);
+                };
+                reply(send, command, &settings[..len]);
+            }
+            Command::RestorePreset => {
+                let (p, dump) = (data[0] as usize, &data[1..]);
+                if p >= PRESETS {
+                    return reply_error(send, cmd.id(), Error::UnknownPreset);
+                }
+                if let Err(e) = check_dump(dump) {
+                    return reply_error(send, cmd.id(), e);
+                }
+                if !self.restore_preset(p, dump) {
+                    return reply_error(send, cmd.id(), Error::BadSettings);
+                }
+                reply(send, command, &[]);
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use motion::{cc, pitch_bend, Sample};
+
+use crate::{
+    state::State,
+    usb::{send_cc, send_pitch_bend},
+};
+
+/// limits for the deadzone and range of `motion::Tilt`, in counts, so they fit in a byte
+pub const MAX_TILT_DEADZONE: i16 = 64;
+pub const MIN_TILT_RANGE: i16 = 32;
+pub const MAX_TILT_RANGE: i16 = 255;
+
+/// What tilting along an axis sends
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TiltTarget {
+    Off,
+    PitchBend,
+    Cc,
+}
+
+impl TiltTarget {
+    pub const fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Off,
+            1 => Self::PitchBend,
+            2 => Self::Cc,
+            _ => panic!("number is not in 0..3"),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct TiltAxis {
+    pub target: TiltTarget,
+    /// CC sent when `target` is `TiltTarget::Cc`
+    pub cc: u8,
+    /// last value sent, so it's only sent again when it changes
+    last: Option<i16>,
+}
+
+impl TiltAxis {
+    const fn new(target: TiltTarget) -> Self {
+        Self {
+            target,
+            cc: 1,
+            last: None,
+        }
+    }
+}
+
+pub struct Tilt {
+    pub enabled: bool,
+    /// tilting left and right, then forward and back
+    pub axes: [TiltAxis; 2],
+    pub motion: motion::Tilt,
+}
+
+impl Tilt {
+    pub const fn new() -> Self {
+        Self {
+            enabled: false,
+            // left and right bends pitch, forward works like a mod wheel
+            axes: [
+                TiltAxis::new(TiltTarget::PitchBend),
+                TiltAxis::new(TiltTarget::Cc),
+            ],
+            motion: motion::Tilt::new(),
+        }
+    }
+}
+
+impl State {
+    /// Sends what the tilt in `sample` maps to, on the selected zone's channel
+    pub fn update_tilt(&mut self, sample: Sample) {
+        if !self.tilt.enabled {
+            return;
+        }
+
+        let values = self.tilt.motion.update(sample);
+        for (axis, value) in values.into_iter().enumerate() {
+            self.send_tilt(axis, value);
+        }
+    }
+
+    /// Sends the resting value of every axis, so turning tilt off doesn't leave the pitch bent
+    pub fn release_tilt(&mut self) {
+        for axis in 0..2 {
+            self.send_tilt(axis, 0);
+        }
+        self.tilt.motion.reset();
+    }
+
+    fn send_tilt(&mut self, axis: usize, value: i16) {
+        let channel = self.zone_channel(self.zone);
+        let tilt = &mut self.tilt.axes[axis];
+        let value = match tilt.target {
+            TiltTarget::Off => return,
+            TiltTarget::PitchBend => pitch_bend(value),
+            TiltTarget::Cc => cc(value) as i16,
+        };
+        if tilt.last == Some(value) {
+            return;
+        }
+        tilt.last = Some(value);
+
+        match tilt.target {
+            TiltTarget::PitchBend => send_pitch_bend(channel, value),
+            _ => send_cc(channel, tilt.cc, value as u8),
+        }
+    }
+}
@@ -9,35 +9,184 @@ use bsp::{
     pac::{MCLK, USB},
     pins::Usb,
 };
-use cortex_m::peripheral::NVIC;
+use cortex_m::peripheral::{DWT, NVIC};
 
 use usb_device::prelude::*;
 use usbd_midi::{
-    data::usb_midi::{
-        midi_packet_reader::MidiPacketBufferReader, usb_midi_event_packet::UsbMidiEventPacket,
-    },
-    midi_device::MidiClass,
+    data::usb_midi::cable_number::CableNumber,
+    midi_types::{Channel, Control, MidiMessage, Note, Value14, Value7},
 };
 use usbd_midi::{
-    data::{usb::constants::USB_CLASS_NONE, usb_midi::cable_number::CableNumber},
-    midi_types::{Channel, MidiMessage, Note, Value7},
+    data::usb_midi::usb_midi_event_packet::UsbMidiEventPacket, midi_device::MidiClass,
 };
+use usbd_serial::SerialPort;
 
 static mut USB_ALLOCATOR: Option<UsbBusAllocator<UsbBus>> = None;
 static mut USB_DEV: Option<UsbDevice<UsbBus>> = None;
 static mut USB_MIDI: Option<MidiClass<UsbBus>> = None;
+static mut USB_SERIAL: Option<SerialPort<UsbBus>> = None;
+
+/// size of the queue of received packets
+const RX_LEN: usize = 64;
+/// packets received in the usb interrupt, waiting to be read by the main loop.
+/// each one is stored with the cycle count it arrived at
+static mut RX: [(u32, [u8; 4]); RX_LEN] = [(0, [0; 4]); RX_LEN];
+static mut RX_HEAD: usize = 0;
+static mut RX_TAIL: usize = 0;
+
+/// time a whole sysex message can take while the host is slow to read it, in cycles at 120MHz.
+/// a full dump usually goes out in a few tens of milliseconds
+const SYSEX_BUDGET: u32 = 120 * 100_000;
+/// time a single message can wait for the host to make room, in cycles at 120MHz.
+/// messages sent back to back, like the ccs of a panic or clock ticks, fill the endpoint
+const MESSAGE_BUDGET: u32 = 120 * 2_000;
+
+/// size of the queue of bytes received on the serial console
+const SERIAL_RX_LEN: usize = 128;
+static mut SERIAL_RX: [u8; SERIAL_RX_LEN] = [0; SERIAL_RX_LEN];
+static mut SERIAL_RX_HEAD: usize = 0;
+static mut SERIAL_RX_TAIL: usize = 0;
+
+/// size of the queue of bytes waiting to be written to the serial console,
+/// enough for the longest command output
+const SERIAL_TX_LEN: usize = 4096;
+static mut SERIAL_TX: [u8; SERIAL_TX_LEN] = [0; SERIAL_TX_LEN];
+static mut SERIAL_TX_USED: usize = 0;
+
+/// Returns the oldest byte received on the serial console
+pub fn read_serial() -> Option<u8> {
+    cortex_m::interrupt::free(|_| unsafe {
+        // a transfer left on the endpoint while the queue was full can come in now
+        if SERIAL_RX_HEAD == SERIAL_RX_TAIL {
+            receive_serial();
+        }
+        if SERIAL_RX_HEAD == SERIAL_RX_TAIL {
+            return None;
+        }
+        let byte = SERIAL_RX[SERIAL_RX_TAIL];
+        SERIAL_RX_TAIL = (SERIAL_RX_TAIL + 1) % SERIAL_RX_LEN;
+        Some(byte)
+    })
+}
+
+/// Queues bytes to be written to the serial console by `flush_serial`, dropping what doesn't fit
+pub fn write_serial(bytes: &[u8]) {
+    unsafe {
+        let len = bytes.len().min(SERIAL_TX_LEN - SERIAL_TX_USED);
+        SERIAL_TX[SERIAL_TX_USED..SERIAL_TX_USED + len].copy_from_slice(&bytes[..len]);
+        SERIAL_TX_USED += len;
+    }
+}
+
+/// Writes as much of the queued console output as the port takes right now, without waiting for the host.
+/// Nothing is written while no terminal has it open
+pub fn flush_serial() {
+    cortex_m::interrupt::free(|_| unsafe {
+        if SERIAL_TX_USED == 0 {
+            return;
+        }
+        let Some(serial) = USB_SERIAL.as_mut() else {
+            return;
+        };
+        let written = if serial.dtr() {
+            match serial.write(&SERIAL_TX[..SERIAL_TX_USED]) {
+                Ok(len) => len,
+                Err(UsbError::WouldBlock) => 0,
+                Err(_) => SERIAL_TX_USED,
+            }
+        } else {
+            SERIAL_TX_USED
+        };
+        SERIAL_TX.copy_within(written..SERIAL_TX_USED, 0);
+        SERIAL_TX_USED -= written;
+    });
+}
+
+/// Returns the oldest received USB MIDI packet, along with the cycle count it arrived at
+pub fn read_packet() -> Option<(u32, [u8; 4])> {
+    cortex_m::interrupt::free(|_| unsafe {
+        // a transfer left on the endpoint while the queue was full can come in now
+        if RX_HEAD == RX_TAIL {
+            receive_midi();
+        }
+        if RX_HEAD == RX_TAIL {
+            return None;
+        }
+        let packet = RX[RX_TAIL];
+        RX_TAIL = (RX_TAIL + 1) % RX_LEN;
+        Some(packet)
+    })
+}
+
+pub fn send_midi(channel: u8, note: Note, vel: u8, on: bool) {
+    let channel = Channel::new(channel);
+    send_message(if on {
+        MidiMessage::NoteOn(channel, note, Value7::new(vel))
+    } else {
+        MidiMessage::NoteOff(channel, note, Value7::new(vel))
+    });
+}
+
+pub fn send_cc(channel: u8, control: u8, value: u8) {
+    send_message(MidiMessage::ControlChange(
+        Channel::new(channel),
+        Control::new(control),
+        Value7::new(value),
+    ));
+}
+
+/// Sends a pitch bend, from -8192 to 8191 with 0 in the center
+pub fn send_pitch_bend(channel: u8, value: i16) {
+    send_message(MidiMessage::PitchBendChange(
+        Channel::new(channel),
+        Value14::from(value),
+    ));
+}
 
-pub fn send_midi(note: Note, vel: u8, on: bool) {
+pub fn send_message(message: MidiMessage) {
     let msg = UsbMidiEventPacket {
         cable_number: CableNumber::Cable0,
-        message: if on {
-            MidiMessage::NoteOn(Channel::new(1), note, Value7::new(vel))
-        } else {
-            MidiMessage::NoteOff(Channel::new(1), note, Value7::new(vel))
-        },
+        message,
     };
+    let packet: [u8; 4] = msg.into();
+    send_packet(packet, DWT::cycle_count(), MESSAGE_BUDGET);
+}
+
+/// Sends a whole sysex message, from `0xf0` to `0xf7`, split into USB MIDI packets.
+/// whatever is left when the host doesn't keep up within `SYSEX_BUDGET` is dropped
+pub fn send_sysex(message: &[u8]) {
+    let start = DWT::cycle_count();
+    let mut chunks = message.chunks(3).peekable();
+    while let Some(chunk) = chunks.next() {
+        // the code index number says whether the sysex continues or ends with this packet
+        let header = match (chunks.peek().is_some(), chunk.len()) {
+            (true, _) => 0x4,
+            (false, 1) => 0x5,
+            (false, 2) => 0x6,
+            (false, _) => 0x7,
+        };
+        let mut packet = [header, 0, 0, 0];
+        packet[1..1 + chunk.len()].copy_from_slice(chunk);
+        if !send_packet(packet, start, SYSEX_BUDGET) {
+            return;
+        }
+    }
+}
+
+/// Sends a raw USB MIDI packet, waiting for the host to make room if needed
+/// until `budget` cycles have gone by since `start`. Returns whether it was sent
+fn send_packet(packet: [u8; 4], start: u32, budget: u32) -> bool {
     unsafe {
-        let _ = USB_MIDI.as_mut().unwrap().send_message(msg);
+        let Some(midi) = USB_MIDI.as_mut() else {
+            return false;
+        };
+        // long messages fill the endpoint faster than the host reads it
+        while DWT::cycle_count().wrapping_sub(start) < budget {
+            if !matches!(midi.send_bytes(packet), Err(UsbError::WouldBlock)) {
+                return true;
+            }
+        }
+        false
     }
 }
 
@@ -76,11 +225,14 @@ pub fn setup_usb(
 
         // set up devices
         USB_MIDI = Some(MidiClass::new(usb_allocator, 1, 1).unwrap());
+        USB_SERIAL = Some(SerialPort::new(usb_allocator));
+        // the serial port groups its two interfaces with an interface association descriptor,
+        // so the device has to say it's a composite that uses them
         USB_DEV = Some(
             UsbDeviceBuilder::new(usb_allocator, UsbVidPid(0x239a, 0x802f))
                 .product("annie midi")
                 .manufacturer("annieversary")
-                .device_class(USB_CLASS_NONE)
+                .composite_with_iads()
                 .build(),
         );
     }
@@ -90,20 +242,60 @@ fn poll_usb() {
     unsafe {
         let Some(usb_dev) = USB_DEV.as_mut()  else {  return;};
         let Some(midi) = USB_MIDI.as_mut() else {  return; };
+        let Some(serial) = USB_SERIAL.as_mut() else {
+            return;
+        };
 
-        if !usb_dev.poll(&mut [midi]) {
+        if !usb_dev.poll(&mut [midi, serial]) {
             return;
         }
 
-        let mut buffer = [0; 64];
+        receive_serial();
+        receive_midi();
+    };
+}
 
-        if let Ok(size) = midi.read(&mut buffer) {
-            let buffer_reader = MidiPacketBufferReader::new(&buffer, size);
-            for _packet in buffer_reader.into_iter().flatten() {
-                // TODO do something here?
-            }
+/// Moves what the serial port received into the queue, as much as fits.
+/// the rest stays with the port and the host waits, so sysex sent over serial doesn't lose bytes
+unsafe fn receive_serial() {
+    let Some(serial) = USB_SERIAL.as_mut() else {
+        return;
+    };
+    let free = (SERIAL_RX_TAIL + SERIAL_RX_LEN - SERIAL_RX_HEAD - 1) % SERIAL_RX_LEN;
+    if free == 0 {
+        return;
+    }
+
+    let mut bytes = [0; 64];
+    let len = free.min(bytes.len());
+    if let Ok(size) = serial.read(&mut bytes[..len]) {
+        for &byte in &bytes[..size] {
+            SERIAL_RX[SERIAL_RX_HEAD] = byte;
+            SERIAL_RX_HEAD = (SERIAL_RX_HEAD + 1) % SERIAL_RX_LEN;
         }
+    }
+}
+
+/// Moves a transfer from the midi endpoint into the queue of received packets.
+/// a transfer is only read once the whole of it fits, otherwise it's left on the endpoint
+/// and the host waits, so long sysex messages like restores don't lose bytes
+unsafe fn receive_midi() {
+    let Some(midi) = USB_MIDI.as_mut() else {
+        return;
     };
+    let free = (RX_TAIL + RX_LEN - RX_HEAD - 1) % RX_LEN;
+    if free < 64 / 4 {
+        return;
+    }
+
+    let mut buffer = [0; 64];
+    if let Ok(size) = midi.read(&mut buffer) {
+        let now = DWT::cycle_count();
+        for packet in buffer[..size].chunks_exact(4) {
+            RX[RX_HEAD] = (now, [packet[0], packet[1], packet[2], packet[3]]);
+            RX_HEAD = (RX_HEAD + 1) % RX_LEN;
+        }
+    }
 }
 
 #[interrupt]